@@ -11,7 +11,7 @@ struct Command {
 
 impl Command {
     fn new(line: String) -> Self {
-        let mut tokens = line.split(" ");
+        let mut tokens = line.split(' ');
 
         let command = tokens.next().unwrap().to_owned();
         let value: ValueType = tokens.next()
@@ -23,6 +23,11 @@ impl Command {
             value,
         }
     }
+
+    // Whether the submarines of part 1 and 2 can follow the command
+    fn is_planar(&self) -> bool {
+        matches!(self.command.as_str(), "forward" | "up" | "down")
+    }
 }
 
 trait Navigation {
@@ -32,16 +37,65 @@ trait Navigation {
 
     fn down(&mut self, value: ValueType);
 
+    fn turn_left(&mut self, _degrees: ValueType) {
+        panic!("{0} does not support turning", std::any::type_name::<Self>());
+    }
+
+    fn turn_right(&mut self, _degrees: ValueType) {
+        panic!("{0} does not support turning", std::any::type_name::<Self>());
+    }
+
+    fn strafe(&mut self, _value: ValueType) {
+        panic!("{0} does not support strafing", std::any::type_name::<Self>());
+    }
+
     fn execute(&mut self, command: Command) {
         match command.command.as_str() {
             "forward" => self.forward(command.value),
             "up" => self.up(command.value),
             "down" => self.down(command.value),
+            "turn-left" => self.turn_left(command.value),
+            "turn-right" => self.turn_right(command.value),
+            "strafe" => self.strafe(command.value),
             _ => panic!("Invalid command: {0:?}", command),
         };
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Heading {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Heading {
+    // Only quarter turns are supported so that positions stay on the integer grid
+    fn turn_right(self, degrees: ValueType) -> Self {
+        assert_eq!(degrees % 90, 0, "Turns must be multiples of 90 degrees: {0:?}", degrees);
+
+        let headings = [Heading::North, Heading::East, Heading::South, Heading::West];
+        let quarter_turns = (degrees / 90).rem_euclid(4) as usize;
+
+        headings[(self as usize + quarter_turns) % 4]
+    }
+
+    fn turn_left(self, degrees: ValueType) -> Self {
+        self.turn_right(-degrees)
+    }
+
+    // Unit vector (x, y) for moving forward; x grows to the east and y to the north
+    fn direction(self) -> (ValueType, ValueType) {
+        match self {
+            Heading::North => (0, 1),
+            Heading::East => (1, 0),
+            Heading::South => (0, -1),
+            Heading::West => (-1, 0),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct Position {
     horizontal: ValueType,
@@ -55,6 +109,14 @@ struct PositionAim {
     aim: ValueType,
 }
 
+#[derive(Debug)]
+struct Position3D {
+    x: ValueType,
+    y: ValueType,
+    depth: ValueType,
+    heading: Heading,
+}
+
 type CommandList = Vec<Command>;
 
 impl Position {
@@ -105,6 +167,60 @@ impl Navigation for PositionAim {
     }
 }
 
+impl Position3D {
+    fn new() -> Self {
+        Position3D {
+            x: ValueType::default(),
+            y: ValueType::default(),
+            depth: ValueType::default(),
+            heading: Heading::East,
+        }
+    }
+
+    fn manhattan_distance(&self) -> ValueType {
+        self.x.abs() + self.y.abs() + self.depth.abs()
+    }
+
+    fn euclidean_distance(&self) -> f64 {
+        let x = self.x as f64;
+        let y = self.y as f64;
+        let depth = self.depth as f64;
+
+        (x * x + y * y + depth * depth).sqrt()
+    }
+}
+
+impl Navigation for Position3D {
+    fn forward(&mut self, delta: ValueType) {
+        let (dx, dy) = self.heading.direction();
+        self.x += dx * delta;
+        self.y += dy * delta;
+    }
+
+    fn up(&mut self, delta: ValueType) {
+        self.depth -= delta;
+    }
+
+    fn down(&mut self, delta: ValueType) {
+        self.depth += delta;
+    }
+
+    fn turn_left(&mut self, degrees: ValueType) {
+        self.heading = self.heading.turn_left(degrees);
+    }
+
+    fn turn_right(&mut self, degrees: ValueType) {
+        self.heading = self.heading.turn_right(degrees);
+    }
+
+    // Positive values strafe to the right of the current heading, negative ones to the left
+    fn strafe(&mut self, delta: ValueType) {
+        let (dx, dy) = self.heading.turn_right(90).direction();
+        self.x += dx * delta;
+        self.y += dy * delta;
+    }
+}
+
 fn part1(commands: CommandList) -> ValueType {
     let mut position = Position::new();
 
@@ -129,12 +245,31 @@ fn part2(commands: CommandList) -> ValueType {
     position.horizontal * position.depth
 }
 
+fn part3(commands: CommandList) -> (ValueType, f64) {
+    let mut position = Position3D::new();
+
+    for command in commands {
+        position.execute(command);
+    }
+
+    println!("position={0:?}", position);
+
+    (position.manhattan_distance(), position.euclidean_distance())
+}
+
 fn main() {
     let commands: CommandList = stdin().lock().lines()
         .map(|line| line.expect("Cannot read line"))
-        .map(|line| Command::new(line))
+        .map(Command::new)
         .collect();
 
+    // Courses that turn or strafe are not the puzzle, and only the 3D submarine can follow them
+    if !commands.iter().all(Command::is_planar) {
+        let (manhattan, euclidean) = part3(commands);
+        println!("part3: manhattan={0:?} euclidean={1:.3}", manhattan, euclidean);
+        return;
+    }
+
     {
         let result = part1(commands.clone());
         println!("part1: result={0:?}", result);
@@ -146,4 +281,10 @@ fn main() {
         println!("part2: result={0:?}", result);
         assert_eq!(result, 1604592846);
     }
+
+    {
+        let (manhattan, euclidean) = part3(commands);
+        println!("part3: manhattan={0:?} euclidean={1:.3}", manhattan, euclidean);
+        assert_eq!(manhattan, 2751);
+    }
 }
@@ -1,122 +1,429 @@
-use std::collections::HashMap;
+use std::fmt;
+use std::fs;
 use std::io::{BufRead, stdin};
 
-fn main() {
-    let binary_numbers: Vec<String> = stdin().lock().lines()
-        .map(|line| line.expect("Cannot read line"))
-        .collect();
+type Reading = u128;
 
-    assert_ne!(binary_numbers.len(), 0);
+const MAX_WIDTH: usize = Reading::BITS as usize;
 
-    let width = binary_numbers[0].len();
+// The product of two readings, which needs up to twice their width
+#[derive(Clone, Copy, PartialEq)]
+struct WideProduct {
+    // Least significant first
+    limbs: [u64; 4],
+}
 
-    let mut gamma_rate_bits: Vec<u32> = Vec::new();
-    gamma_rate_bits.reserve_exact(width);
+impl WideProduct {
+    fn of(lhs: Reading, rhs: Reading) -> Self {
+        let split = |value: u128| [value as u64, (value >> 64) as u64];
+        let (lhs, rhs) = (split(lhs), split(rhs));
+        let mut limbs = [0; 4];
 
-    let mut epsilon_rate_bits: Vec<u32> = Vec::new();
-    epsilon_rate_bits.reserve_exact(width);
+        for (i, &lhs_limb) in lhs.iter().enumerate() {
+            let mut carry = 0;
 
-    let mut oxygen_generator_rating = binary_numbers.clone();
-    let mut co2_scrubber_rating = binary_numbers.clone();
+            for (j, &rhs_limb) in rhs.iter().enumerate() {
+                let total = limbs[i + j] as u128 + lhs_limb as u128 * rhs_limb as u128 + carry;
+                limbs[i + j] = total as u64;
+                carry = total >> 64;
+            }
 
-    let mut column: usize = 0;
+            limbs[i + 2] = carry as u64;
+        }
 
-    while column < width {
-        let counter = character_count_by_column(&binary_numbers, column);
+        Self {
+            limbs,
+        }
+    }
+}
 
-        // println!("column={0:?} counter={1:?}", column, counter);
+impl PartialEq<u128> for WideProduct {
+    fn eq(&self, other: &u128) -> bool {
+        *self == Self::of(*other, 1)
+    }
+}
 
-        let zeroes = counter.get("0").unwrap();
-        let ones = counter.get("1").unwrap();
+impl fmt::Debug for WideProduct {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const CHUNK: u128 = 10_000_000_000_000_000_000;
 
-        let (gamma_rate_bit, epsilon_rate_bit) = if zeroes > ones { (0, 1) } else { (1, 0) };
+        // Repeatedly divide by 10^19, collecting the remainders as decimal chunks
+        let mut limbs = self.limbs;
+        let mut chunks = Vec::new();
 
-        gamma_rate_bits.push(gamma_rate_bit);
-        epsilon_rate_bits.push(epsilon_rate_bit);
+        loop {
+            let mut remainder = 0;
 
-        if oxygen_generator_rating.len() > 1 {
-            let counter = character_count_by_column(&oxygen_generator_rating, column);
-            let zeroes = counter.get("0").unwrap_or(&0);
-            let ones = counter.get("1").unwrap_or(&0);
-            let higher_occurrence = if zeroes > ones { "0" } else { "1" };
+            for limb in limbs.iter_mut().rev() {
+                let current = (remainder << 64) | *limb as u128;
+                *limb = (current / CHUNK) as u64;
+                remainder = current % CHUNK;
+            }
 
-            oxygen_generator_rating = filter_by_column(&oxygen_generator_rating, column, higher_occurrence);
-        }
+            chunks.push(remainder);
 
-        if co2_scrubber_rating.len() > 1 {
-            let counter = character_count_by_column(&co2_scrubber_rating, column);
-            let zeroes = counter.get("0").unwrap_or(&0);
-            let ones = counter.get("1").unwrap_or(&0);
-            let lower_occurrence = if zeroes > ones { "1" } else { "0" };
+            if limbs.iter().all(|&limb| limb == 0) {
+                break;
+            }
+        }
 
-            co2_scrubber_rating = filter_by_column(&co2_scrubber_rating, column, lower_occurrence);
+        write!(f, "{0}", chunks.pop().unwrap())?;
+        for chunk in chunks.iter().rev() {
+            write!(f, "{0:019}", chunk)?;
         }
 
-        // println!("oxygen_generator_rating={0:?}", oxygen_generator_rating);
-        // println!("co2_scrubber_rating={0:?}", co2_scrubber_rating);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Selection {
+    MostCommon,
+    LeastCommon,
+}
 
-        column += 1;
+#[derive(Clone, Debug)]
+struct RatingCriteria {
+    name: String,
+    selection: Selection,
+    tie_break: bool,
+}
+
+impl RatingCriteria {
+    fn new(name: &str, selection: Selection, tie_break: bool) -> Self {
+        Self {
+            name: name.to_owned(),
+            selection,
+            tie_break,
+        }
     }
 
-    // println!("gamma_rate_bits={0:?} epsilon_rate_bits={1:?}", gamma_rate_bits, epsilon_rate_bits);
+    fn oxygen_generator() -> Self {
+        Self::new("oxygen_generator_rating", Selection::MostCommon, true)
+    }
 
-    let gamma_rate = to_decimal(&gamma_rate_bits);
-    println!("gamma_rate={0:?}", gamma_rate);
+    fn co2_scrubber() -> Self {
+        Self::new("co2_scrubber_rating", Selection::LeastCommon, false)
+    }
 
-    let epsilon_rate = to_decimal(&epsilon_rate_bits);
-    println!("epsilon_rate={0:?}", epsilon_rate);
+    // Format: `<name> <most|least> <tie-break bit>`, e.g. `oxygen_generator_rating most 1`
+    fn from_line(line: &str) -> Self {
+        let mut tokens = line.split_whitespace();
 
-    let result = gamma_rate * epsilon_rate;
-    println!("part1: result={0:?}", result);
-    assert_eq!(result, 2640986);
+        let name = tokens.next().expect("Could not determine rating name");
+        let selection = match tokens.next().expect("Could not determine selection") {
+            "most" => Selection::MostCommon,
+            "least" => Selection::LeastCommon,
+            selection => panic!("Invalid selection: {0:?}", selection),
+        };
+        let tie_break = match tokens.next().expect("Could not determine tie-break value") {
+            "0" => false,
+            "1" => true,
+            tie_break => panic!("Invalid tie-break value: {0:?}", tie_break),
+        };
 
-    let oxygen_generator_rating: Vec<u32> = oxygen_generator_rating.first().map(|s| string_to_digit_vector(&s)).unwrap();
-    let oxygen_generator_rating = to_decimal(&oxygen_generator_rating);
-    println!("oxygen_generator_rating={0:?}", oxygen_generator_rating);
+        Self::new(name, selection, tie_break)
+    }
 
-    let co2_scrubber_rating: Vec<u32> = co2_scrubber_rating.first().map(|s| string_to_digit_vector(&s)).unwrap();
-    let co2_scrubber_rating = to_decimal(&co2_scrubber_rating);
-    println!("co2_scrubber_rating={0:?}", co2_scrubber_rating);
+    fn from_file(path: &str) -> Vec<Self> {
+        fs::read_to_string(path)
+            .unwrap_or_else(|error| panic!("Cannot read ratings from {0:?}: {1}", path, error))
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Self::from_line)
+            .collect()
+    }
 
-    let result = oxygen_generator_rating * co2_scrubber_rating;
-    println!("part2: result={0:?}", result);
-    assert_eq!(result, 6822109);
+    fn keep_ones(&self, ones: usize, zeroes: usize) -> bool {
+        if ones == zeroes {
+            return self.tie_break;
+        }
+
+        match self.selection {
+            Selection::MostCommon => ones > zeroes,
+            Selection::LeastCommon => ones < zeroes,
+        }
+    }
 }
 
-fn string_to_digit_vector(input: &String) -> Vec<u32> {
-    input.chars().map(|c| c.to_digit(10).unwrap()).collect()
+#[derive(Debug)]
+struct DiagnosticReport {
+    readings: Vec<Reading>,
+    width: usize,
 }
 
-fn character_count_by_column(lines: &Vec<String>, column: usize) -> HashMap<&str, u32> {
-    let mut counter = HashMap::new();
+impl DiagnosticReport {
+    fn from_lines(lines: &[String]) -> Self {
+        assert_ne!(lines.len(), 0);
+
+        let width = lines[0].len();
+        assert!(width > 0 && width <= MAX_WIDTH, "Unsupported reading width: {0:?}", width);
+
+        let readings = lines.iter()
+            .map(|line| {
+                assert_eq!(line.len(), width, "Inconsistent reading width: {0:?}", line);
+                Reading::from_str_radix(line, 2).expect("Cannot parse reading")
+            })
+            .collect();
+
+        Self {
+            readings,
+            width,
+        }
+    }
+
+    // Columns are numbered from the most significant (leftmost) bit, as they appear in the input
+    #[inline]
+    fn column_mask(&self, column: usize) -> Reading {
+        1 << (self.width - 1 - column)
+    }
+
+    fn ones_in_column(readings: &[Reading], mask: Reading) -> usize {
+        readings.iter()
+            .filter(|&&reading| reading & mask != 0)
+            .count()
+    }
+
+    fn ones_by_column(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.width];
+
+        for &reading in &self.readings {
+            let mut remaining = reading;
+
+            while remaining != 0 {
+                let bit = remaining.trailing_zeros() as usize;
+                counts[self.width - 1 - bit] += 1;
+                remaining &= remaining - 1;
+            }
+        }
+
+        counts
+    }
+
+    fn width_mask(&self) -> Reading {
+        Reading::MAX >> (MAX_WIDTH - self.width)
+    }
+
+    fn gamma_rate(&self) -> Reading {
+        let total = self.readings.len();
+
+        self.ones_by_column().iter()
+            .enumerate()
+            .filter(|&(_column, &ones)| ones >= total - ones)
+            .fold(0, |gamma_rate, (column, _ones)| gamma_rate | self.column_mask(column))
+    }
+
+    fn epsilon_rate(&self) -> Reading {
+        !self.gamma_rate() & self.width_mask()
+    }
+
+    // Calls `on_column` with the candidates remaining after filtering by each column
+    fn rating_with<F>(&self, criteria: &RatingCriteria, mut on_column: F) -> Reading
+        where F: FnMut(usize, &[Reading])
+    {
+        let mut candidates = self.readings.clone();
+
+        for column in 0..self.width {
+            if candidates.len() <= 1 {
+                break;
+            }
+
+            let mask = self.column_mask(column);
+            let ones = Self::ones_in_column(&candidates, mask);
+            let zeroes = candidates.len() - ones;
+
+            let wanted = if criteria.keep_ones(ones, zeroes) { mask } else { 0 };
+
+            candidates.retain(|&reading| reading & mask == wanted);
+            on_column(column, &candidates);
+        }
+
+        *candidates.first()
+            .unwrap_or_else(|| panic!("No reading matches the bit criteria of {0:?}", criteria.name))
+    }
 
-    for line in lines {
-        let digit = line.get(column..column + 1).unwrap();
-        *counter.entry(digit).or_insert(0) += 1;
+    fn rating(&self, criteria: &RatingCriteria) -> Reading {
+        self.rating_with(criteria, |_column, _candidates| ())
     }
 
-    counter
+    fn print_rating_report(&self, criteria: &RatingCriteria) {
+        println!("rating={0:?} selection={1:?} tie_break={2:?}", criteria.name, criteria.selection, criteria.tie_break as u8);
+
+        let rating = self.rating_with(criteria, |column, candidates| {
+            let candidates = candidates.iter()
+                .map(|reading| format!("{0:01$b}", reading, self.width))
+                .collect::<Vec<String>>()
+                .join(" ");
+            println!("  column={0:?} candidates=[{1}]", column, candidates);
+        });
+
+        println!("  {0}={1:?}", criteria.name, rating);
+    }
 }
 
-fn filter_by_column(lines: &Vec<String>, column: usize, filter_by: &str) -> Vec<String> {
-    lines.iter()
-        .filter(|line| line.get(column..column + 1).unwrap() == filter_by)
-        .map(|line| line.to_owned())
-        .collect()
+#[derive(Clone, Copy, Debug, Default)]
+struct TrieNode {
+    // Index of the child node for a 0 and 1 bit respectively; 0 means there is none, as that is the root
+    children: [u32; 2],
+    count: usize,
 }
 
-fn to_decimal(bits: &Vec<u32>) -> u32 {
-    let mut result = 0;
+// Binary trie over all readings, from the most significant bit down, where every node counts the readings below it
+#[derive(Debug)]
+struct ReadingTrie {
+    nodes: Vec<TrieNode>,
+    width: usize,
+}
 
-    for (index, bit) in bits.iter().rev().enumerate() {
-        let value = if index == 0 && *bit == 0 {
-            0
-        } else {
-            u32::pow(*bit * 2, index as u32)
+impl ReadingTrie {
+    fn from_report(report: &DiagnosticReport) -> Self {
+        let mut trie = Self {
+            nodes: vec![TrieNode::default()],
+            width: report.width,
         };
-        // println!("index={0:?} bit={1:?} w={2:?}", index, bit, g);
-        result += value;
+
+        for &reading in &report.readings {
+            trie.insert(reading);
+        }
+
+        trie
+    }
+
+    fn insert(&mut self, reading: Reading) {
+        let mut node = 0;
+        self.nodes[node].count += 1;
+
+        for column in 0..self.width {
+            let bit = self.bit(reading, column);
+            let mut child = self.nodes[node].children[bit] as usize;
+
+            if child == 0 {
+                child = self.nodes.len();
+                self.nodes.push(TrieNode::default());
+                self.nodes[node].children[bit] = u32::try_from(child).expect("Too many trie nodes");
+            }
+
+            node = child;
+            self.nodes[node].count += 1;
+        }
+    }
+
+    #[inline]
+    fn bit(&self, reading: Reading, column: usize) -> usize {
+        ((reading >> (self.width - 1 - column)) & 1) as usize
+    }
+
+    fn count_with_prefix(&self, prefix: &str) -> usize {
+        assert!(prefix.len() <= self.width, "Prefix is longer than the readings: {0:?}", prefix);
+
+        let mut node = 0;
+
+        for c in prefix.chars() {
+            let bit = match c {
+                '0' => 0,
+                '1' => 1,
+                _ => panic!("Invalid prefix: {0:?}", prefix),
+            };
+
+            node = match self.nodes[node].children[bit] {
+                0 => return 0,
+                child => child as usize,
+            };
+        }
+
+        self.nodes[node].count
+    }
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    ratings_path: Option<String>,
+    report: bool,
+    prefixes: Vec<String>,
+}
+
+impl Options {
+    fn from_args() -> Self {
+        let mut options = Self::default();
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--ratings" => options.ratings_path = Some(args.next().expect("Missing path for --ratings")),
+                "--report" => options.report = true,
+                "--prefix" => options.prefixes.push(args.next().expect("Missing bits for --prefix")),
+                _ => panic!("Invalid argument: {0:?}", arg),
+            }
+        }
+
+        options
+    }
+}
+
+fn main() {
+    let options = Options::from_args();
+
+    let lines: Vec<String> = stdin().lock().lines()
+        .map(|line| line.expect("Cannot read line"))
+        .collect();
+
+    let report = DiagnosticReport::from_lines(&lines);
+
+    // Only the puzzle report of 1000 twelve-bit readings has known answers, larger ones and the extra modes do not
+    let is_puzzle = report.readings.len() == 1000 && report.width == 12
+        && options.ratings_path.is_none() && !options.report && options.prefixes.is_empty();
+
+    let gamma_rate = report.gamma_rate();
+    println!("gamma_rate={0:?}", gamma_rate);
+
+    let epsilon_rate = report.epsilon_rate();
+    println!("epsilon_rate={0:?}", epsilon_rate);
+
+    let result = WideProduct::of(gamma_rate, epsilon_rate);
+    println!("part1: result={0:?}", result);
+
+    if is_puzzle {
+        assert_eq!(result, 2640986);
+    }
+
+    let oxygen_generator_criteria = RatingCriteria::oxygen_generator();
+    let co2_scrubber_criteria = RatingCriteria::co2_scrubber();
+
+    let oxygen_generator_rating = report.rating(&oxygen_generator_criteria);
+    println!("oxygen_generator_rating={0:?}", oxygen_generator_rating);
+
+    let co2_scrubber_rating = report.rating(&co2_scrubber_criteria);
+    println!("co2_scrubber_rating={0:?}", co2_scrubber_rating);
+
+    let result = WideProduct::of(oxygen_generator_rating, co2_scrubber_rating);
+    println!("part2: result={0:?}", result);
+
+    if is_puzzle {
+        assert_eq!(result, 6822109);
+    }
+
+    let mut ratings = vec![oxygen_generator_criteria, co2_scrubber_criteria];
+
+    if let Some(path) = &options.ratings_path {
+        for criteria in RatingCriteria::from_file(path) {
+            println!("{0}={1:?}", criteria.name, report.rating(&criteria));
+            ratings.push(criteria);
+        }
+    }
+
+    // The trie takes a node per bit of every reading, so it is only built when it is needed
+    if !options.prefixes.is_empty() {
+        let trie = ReadingTrie::from_report(&report);
+
+        for prefix in &options.prefixes {
+            println!("prefix={0:?} count={1:?}", prefix, trie.count_with_prefix(prefix));
+        }
     }
 
-    result
+    if options.report {
+        for criteria in &ratings {
+            report.print_rating_report(criteria);
+        }
+    }
 }
@@ -1,4 +1,5 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
 use std::io::{BufRead, stdin};
 
 #[derive(Debug)]
@@ -16,92 +17,659 @@ impl BingoBoardEntry {
     }
 }
 
+// A set of cells that wins the board once all of them are marked
+#[derive(Clone, Debug)]
+struct Pattern {
+    name: String,
+    cells: Vec<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum WinningRule {
+    Rows,
+    Columns,
+    Diagonals,
+    Corners,
+    FullHouse,
+    // Rows of the mask separated by '/', with '1' for the cells that must be marked, e.g. `10001/01010/00100/01010/10001`
+    Mask(Vec<String>),
+}
+
+impl WinningRule {
+    fn from_token(token: &str) -> Self {
+        match token {
+            "rows" => WinningRule::Rows,
+            "columns" => WinningRule::Columns,
+            "diagonals" => WinningRule::Diagonals,
+            "corners" => WinningRule::Corners,
+            "full-house" => WinningRule::FullHouse,
+            _ => match token.strip_prefix("mask:") {
+                Some(mask) => WinningRule::Mask(mask.split('/').map(|row| row.to_owned()).collect()),
+                None => panic!("Invalid winning rule: {0:?}", token),
+            },
+        }
+    }
+
+    fn patterns(&self, width: usize, height: usize) -> Vec<Pattern> {
+        let index = |row: usize, column: usize| row * width + column;
+
+        match self {
+            WinningRule::Rows => (0..height)
+                .map(|row| Pattern {
+                    name: format!("row {0}", row),
+                    cells: (0..width).map(|column| index(row, column)).collect(),
+                })
+                .collect(),
+            WinningRule::Columns => (0..width)
+                .map(|column| Pattern {
+                    name: format!("column {0}", column),
+                    cells: (0..height).map(|row| index(row, column)).collect(),
+                })
+                .collect(),
+            WinningRule::Diagonals => {
+                assert_eq!(width, height, "Diagonals require a square board");
+
+                vec![
+                    Pattern {
+                        name: "diagonal".to_owned(),
+                        cells: (0..width).map(|i| index(i, i)).collect(),
+                    },
+                    Pattern {
+                        name: "anti-diagonal".to_owned(),
+                        cells: (0..width).map(|i| index(i, width - 1 - i)).collect(),
+                    },
+                ]
+            }
+            WinningRule::Corners => {
+                let mut cells = vec![
+                    index(0, 0),
+                    index(0, width - 1),
+                    index(height - 1, 0),
+                    index(height - 1, width - 1),
+                ];
+                cells.sort_unstable();
+                cells.dedup();
+
+                vec![Pattern { name: "corners".to_owned(), cells }]
+            }
+            WinningRule::FullHouse => vec![Pattern {
+                name: "full house".to_owned(),
+                cells: (0..width * height).collect(),
+            }],
+            WinningRule::Mask(rows) => {
+                assert_eq!(rows.len(), height, "Mask height does not match the board: {0:?}", rows);
+
+                let mut cells = Vec::new();
+
+                for (row, mask_row) in rows.iter().enumerate() {
+                    assert_eq!(mask_row.len(), width, "Mask width does not match the board: {0:?}", mask_row);
+
+                    for (column, c) in mask_row.chars().enumerate() {
+                        match c {
+                            '1' => cells.push(index(row, column)),
+                            '0' => (),
+                            _ => panic!("Invalid mask cell {0:?} in {1:?}", c, mask_row),
+                        }
+                    }
+                }
+
+                assert!(!cells.is_empty(), "Mask does not contain any cell: {0:?}", rows);
+
+                vec![Pattern { name: format!("mask {0}", rows.join("/")), cells }]
+            }
+        }
+    }
+}
+
+// All winning patterns of a board size, plus which patterns each cell takes part in
+#[derive(Debug)]
+struct PatternIndex {
+    patterns: Vec<Pattern>,
+    patterns_by_cell: Vec<Vec<usize>>,
+}
+
+impl PatternIndex {
+    fn new(rules: &[WinningRule], width: usize, height: usize) -> Self {
+        let patterns: Vec<Pattern> = rules.iter()
+            .flat_map(|rule| rule.patterns(width, height))
+            .collect();
+
+        let mut patterns_by_cell = vec![Vec::new(); width * height];
+
+        for (pattern_index, pattern) in patterns.iter().enumerate() {
+            for &cell in &pattern.cells {
+                patterns_by_cell[cell].push(pattern_index);
+            }
+        }
+
+        Self {
+            patterns,
+            patterns_by_cell,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MarkResult {
+    Unchanged,
+    Marked,
+    // Index of the pattern that has been completed by the mark
+    Completed(usize),
+}
+
 #[derive(Debug)]
 struct BingoBoard {
     width: usize,
     height: usize,
     entries: Vec<BingoBoardEntry>,
+    positions: HashMap<u32, usize>,
+    marked_by_pattern: Vec<usize>,
+    winning_pattern: Option<usize>,
 }
 
 impl BingoBoard {
-    fn from_lines(lines: &mut VecDeque<String>) -> Self {
-        let width = 5;
-        let height = 5;
+    // Without an explicit size, the board ends at the next empty line and its width is taken from the first row
+    fn from_lines(lines: &mut VecDeque<String>, size: Option<(usize, usize)>) -> Self {
+        let height = match size {
+            Some((_width, height)) => height,
+            None => lines.iter().take_while(|line| !line.trim().is_empty()).count(),
+        };
+        let width = match size {
+            Some((width, _height)) => width,
+            None => lines.front().map_or(0, |line| line.split_whitespace().count()),
+        };
+        assert!(width > 0 && height > 0, "Could not determine size of bingo board");
+
         let mut entries = Vec::new();
 
         entries.reserve_exact(width * height);
 
         for row in 0..height {
             let current_line = lines.pop_front()
-                .expect(format!("Could not read row {0} for bingo board", row).as_str());
+                .unwrap_or_else(|| panic!("Could not read row {0} for bingo board", row));
 
-            let mut current_line = current_line.split_whitespace().into_iter();
+            let mut current_line = current_line.split_whitespace();
 
             for column in 0..width {
                 let number = current_line.next()
-                    .expect(format!("Could not read ({0}, {1})", row, column).as_str());
+                    .unwrap_or_else(|| panic!("Could not read ({0}, {1})", row, column));
 
                 let number = number.parse::<u32>()
-                    .expect(format!("Could not parse '{0}' into number at ({1}, {2})", number, row, column).as_str());
+                    .unwrap_or_else(|_| panic!("Could not parse '{0}' into number at ({1}, {2})", number, row, column));
 
                 entries.push(BingoBoardEntry::new(number));
             }
+
+            assert!(current_line.next().is_none(), "Row {0} is wider than {1} columns", row, width);
+        }
+
+        let mut positions = HashMap::new();
+
+        // A number appearing more than once on a board only marks its first occurrence
+        for (index, entry) in entries.iter().enumerate().rev() {
+            positions.insert(entry.value, index);
         }
 
         Self {
             width,
             height,
             entries,
+            positions,
+            marked_by_pattern: Vec::new(),
+            winning_pattern: None,
         }
     }
 
-    fn mark(&mut self, number: u32) {
+    fn reset(&mut self, pattern_index: &PatternIndex) {
         for entry in &mut self.entries {
-            if entry.value == number {
-                entry.marked = true;
-                break;
+            entry.marked = false;
+        }
+
+        self.marked_by_pattern = vec![0; pattern_index.patterns.len()];
+        self.winning_pattern = None;
+    }
+
+    fn mark(&mut self, number: u32, pattern_index: &PatternIndex) -> MarkResult {
+        let Some(&index) = self.positions.get(&number) else {
+            return MarkResult::Unchanged;
+        };
+        let entry = &mut self.entries[index];
+
+        if entry.marked {
+            return MarkResult::Unchanged;
+        }
+
+        entry.marked = true;
+
+        let mut completed = None;
+
+        for &pattern in &pattern_index.patterns_by_cell[index] {
+            self.marked_by_pattern[pattern] += 1;
+
+            if completed.is_none() && self.marked_by_pattern[pattern] == pattern_index.patterns[pattern].cells.len() {
+                completed = Some(pattern);
             }
         }
+
+        if self.winning_pattern.is_none() {
+            self.winning_pattern = completed;
+        }
+
+        match completed {
+            Some(pattern) => MarkResult::Completed(pattern),
+            None => MarkResult::Marked,
+        }
     }
 
     fn is_bingo(&self) -> bool {
-        'next_column: for column in 0..self.width {
-            for row in 0..self.height {
-                let index = self.get_index(row, column);
-                let entry = self.entries.get(index).unwrap();
+        self.winning_pattern.is_some()
+    }
+}
 
-                if entry.marked == false {
-                    continue 'next_column;
-                }
+#[derive(Clone, Copy, Debug)]
+struct Win {
+    board: usize,
+    draw: usize,
+    number: u32,
+    pattern: usize,
+    score: u32,
+}
+
+#[derive(Debug)]
+struct BingoGame {
+    boards: Vec<BingoBoard>,
+    pattern_index: PatternIndex,
+    boards_by_number: HashMap<u32, Vec<usize>>,
+}
+
+impl BingoGame {
+    fn new(boards: Vec<BingoBoard>, rules: &[WinningRule]) -> Self {
+        let (width, height) = boards.first()
+            .map(|board| (board.width, board.height))
+            .expect("Could not read any bingo board");
+
+        assert!(boards.iter().all(|board| board.width == width && board.height == height),
+                "All bingo boards must have the same size");
+
+        let pattern_index = PatternIndex::new(rules, width, height);
+
+        let mut boards_by_number: HashMap<u32, Vec<usize>> = HashMap::new();
+
+        for (board_index, board) in boards.iter().enumerate() {
+            for &number in board.positions.keys() {
+                boards_by_number.entry(number).or_default().push(board_index);
             }
+        }
+
+        Self {
+            boards,
+            pattern_index,
+            boards_by_number,
+        }
+    }
 
-            // All entries in this column have been marked
-            return true;
+    fn play(&mut self, drawn_numbers: &[u32]) -> Vec<Win> {
+        self.play_with(drawn_numbers, |_draw, _board| ())
+    }
+
+    // Boards stop being marked once they have won; wins are returned in the order they happen.
+    // Calls `on_mark` with the draw index and board index for every cell that gets marked.
+    fn play_with<F>(&mut self, drawn_numbers: &[u32], mut on_mark: F) -> Vec<Win>
+        where F: FnMut(usize, usize)
+    {
+        for board in &mut self.boards {
+            board.reset(&self.pattern_index);
         }
 
-        'next_row: for row in 0..self.height {
-            for column in 0..self.width {
-                let index = self.get_index(row, column);
-                let entry = self.entries.get(index).unwrap();
+        let mut wins = Vec::new();
+
+        for (draw, &number) in drawn_numbers.iter().enumerate() {
+            let Some(board_indices) = self.boards_by_number.get(&number) else {
+                continue;
+            };
+
+            for &board_index in board_indices {
+                let board = &mut self.boards[board_index];
+
+                if board.is_bingo() {
+                    continue;
+                }
 
-                if entry.marked == false {
-                    continue 'next_row;
+                match board.mark(number, &self.pattern_index) {
+                    MarkResult::Unchanged => (),
+                    MarkResult::Marked => on_mark(draw, board_index),
+                    MarkResult::Completed(pattern) => {
+                        on_mark(draw, board_index);
+
+                        wins.push(Win {
+                            board: board_index,
+                            draw,
+                            number,
+                            pattern,
+                            score: determine_result(board, number),
+                        });
+                    }
                 }
             }
 
-            // All entries in this row have been marked
-            return true;
+            if wins.len() == self.boards.len() {
+                break;
+            }
+        }
+
+        wins
+    }
+}
+
+#[derive(Debug, Default)]
+struct DrawLog {
+    number: u32,
+    marked: Vec<usize>,
+    completed: Vec<usize>,
+}
+
+// Everything that happened during one game, draw by draw and board by board
+#[derive(Debug)]
+struct GameLog {
+    draws: Vec<DrawLog>,
+    wins: Vec<Win>,
+    board_count: usize,
+}
+
+impl GameLog {
+    fn record(game: &mut BingoGame, drawn_numbers: &[u32]) -> Self {
+        let mut draws: Vec<DrawLog> = drawn_numbers.iter()
+            .map(|&number| DrawLog { number, ..DrawLog::default() })
+            .collect();
+
+        let wins = game.play_with(drawn_numbers, |draw, board| draws[draw].marked.push(board));
+
+        for win in &wins {
+            draws[win.draw].completed.push(win.board);
+        }
+
+        // Drawing stops once every board has won
+        let played_draws = wins.iter()
+            .map(|win| win.draw + 1)
+            .max()
+            .filter(|_| wins.len() == game.boards.len())
+            .unwrap_or(drawn_numbers.len());
+        draws.truncate(played_draws);
+
+        Self {
+            draws,
+            wins,
+            board_count: game.boards.len(),
+        }
+    }
+
+    fn to_json(&self, pattern_index: &PatternIndex) -> String {
+        let pattern_name = |pattern: usize| json_string(&pattern_index.patterns[pattern].name);
+        let join = |values: &[usize]| values.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(", ");
+
+        let mut win_by_board: Vec<Option<&Win>> = vec![None; self.board_count];
+        for win in &self.wins {
+            win_by_board[win.board] = Some(win);
+        }
+
+        let mut json = String::from("{\n  \"draws\": [");
+
+        for (draw, draw_log) in self.draws.iter().enumerate() {
+            let completed = draw_log.completed.iter()
+                .map(|&board| {
+                    let win = win_by_board[board].unwrap();
+                    format!("{{\"board\": {0}, \"pattern\": {1}}}", board, pattern_name(win.pattern))
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            json += if draw == 0 { "\n" } else { ",\n" };
+            json += &format!("    {{\"draw\": {0}, \"number\": {1}, \"marked\": [{2}], \"completed\": [{3}]}}",
+                             draw, draw_log.number, join(&draw_log.marked), completed);
+        }
+
+        json += "\n  ],\n  \"boards\": [";
+
+        // Boards are ranked by win order, boards that never win come last
+        let mut timeline: Vec<String> = self.wins.iter()
+            .enumerate()
+            .map(|(rank, win)| format!(
+                "    {{\"board\": {0}, \"rank\": {1}, \"draw\": {2}, \"number\": {3}, \"pattern\": {4}, \"score\": {5}}}",
+                win.board, rank + 1, win.draw, win.number, pattern_name(win.pattern), win.score))
+            .collect();
+
+        for (board, win) in win_by_board.iter().enumerate() {
+            if win.is_none() {
+                timeline.push(format!(
+                    "    {{\"board\": {0}, \"rank\": null, \"draw\": null, \"number\": null, \"pattern\": null, \"score\": null}}",
+                    board));
+            }
+        }
+
+        if !timeline.is_empty() {
+            json += "\n";
+            json += &timeline.join(",\n");
+        }
+
+        json += "\n  ]\n}\n";
+        json
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut result = String::from("\"");
+
+    for c in value.chars() {
+        match c {
+            '"' => result += "\\\"",
+            '\\' => result += "\\\\",
+            c if c.is_control() => result += &format!("\\u{0:04x}", c as u32),
+            c => result.push(c),
+        }
+    }
+
+    result.push('"');
+    result
+}
+
+// SplitMix64, so that simulations are reproducible from a seed without pulling in a dependency
+#[derive(Debug)]
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in 0..bound, rejecting the values that would bias the modulo
+    fn below(&mut self, bound: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % bound;
+
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return value % bound;
+            }
+        }
+    }
+
+    fn shuffle<T>(&mut self, values: &mut [T]) {
+        for i in (1..values.len()).rev() {
+            let j = self.below(i as u64 + 1) as usize;
+            values.swap(i, j);
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct BoardStatistics {
+    first_wins: usize,
+    last_wins: usize,
+    wins: usize,
+    draw_sum: f64,
+    draw_square_sum: f64,
+}
+
+// z-score of a two-sided 95% confidence interval
+const Z_95: f64 = 1.959964;
+
+impl BoardStatistics {
+    // Wilson score interval of a win probability
+    fn probability(successes: usize, trials: usize) -> (f64, f64, f64) {
+        let n = trials as f64;
+        let p = successes as f64 / n;
+        let z2 = Z_95 * Z_95;
+
+        let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+        let margin = Z_95 * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / (1.0 + z2 / n);
+
+        (p, center - margin, center + margin)
+    }
+
+    fn expected_draw(&self) -> Option<(f64, f64)> {
+        if self.wins == 0 {
+            return None;
+        }
+
+        let n = self.wins as f64;
+        let mean = self.draw_sum / n;
+        let variance = if self.wins > 1 {
+            ((self.draw_square_sum - n * mean * mean) / (n - 1.0)).max(0.0)
+        } else {
+            0.0
+        };
+
+        Some((mean, Z_95 * (variance / n).sqrt()))
+    }
+}
+
+// Replays the game with shuffled draw orders. Boards tied on the first (or last) winning draw all count as
+// winning first (or last), so the probabilities over all boards may add up to more than 1.
+fn simulate(game: &mut BingoGame, drawn_numbers: &[u32], iterations: usize, seed: u64) -> Vec<BoardStatistics> {
+    let mut rng = Rng::new(seed);
+    let mut drawn_numbers = drawn_numbers.to_vec();
+    let mut statistics = vec![BoardStatistics::default(); game.boards.len()];
+
+    for _iteration in 0..iterations {
+        rng.shuffle(&mut drawn_numbers);
+
+        let wins = game.play(&drawn_numbers);
+
+        let (Some(first), Some(last)) = (wins.first(), wins.last()) else {
+            continue;
+        };
+
+        for win in &wins {
+            let board_statistics = &mut statistics[win.board];
+            let draw = win.draw as f64;
+
+            board_statistics.wins += 1;
+            board_statistics.draw_sum += draw;
+            board_statistics.draw_square_sum += draw * draw;
+
+            if win.draw == first.draw {
+                board_statistics.first_wins += 1;
+            }
+        }
+
+        // Only a board that wins on the very last winning draw, after all other boards have won, wins last
+        if wins.len() == game.boards.len() {
+            for win in wins.iter().rev().take_while(|win| win.draw == last.draw) {
+                statistics[win.board].last_wins += 1;
+            }
         }
+    }
+
+    statistics
+}
 
-        false
+fn print_simulation(statistics: &[BoardStatistics], iterations: usize) {
+    for (board, board_statistics) in statistics.iter().enumerate() {
+        let (p_first, p_first_low, p_first_high) = BoardStatistics::probability(board_statistics.first_wins, iterations);
+        let (p_last, p_last_low, p_last_high) = BoardStatistics::probability(board_statistics.last_wins, iterations);
+
+        let expected_draw = match board_statistics.expected_draw() {
+            Some((mean, margin)) => format!("{0:.2} ± {1:.2}", mean, margin),
+            None => "never".to_owned(),
+        };
+
+        println!("board={0:?} p_first={1:.4} [{2:.4}, {3:.4}] p_last={4:.4} [{5:.4}, {6:.4}] expected_draw={7}",
+                 board, p_first, p_first_low, p_first_high, p_last, p_last_low, p_last_high, expected_draw);
     }
+}
+
+#[derive(Debug)]
+struct Options {
+    size: Option<(usize, usize)>,
+    rules: Vec<WinningRule>,
+    log_path: Option<String>,
+    simulations: Option<usize>,
+    seed: u64,
+}
 
-    fn get_index(&self, row: usize, column: usize) -> usize {
-        row * self.width + column
+impl Options {
+    fn from_args() -> Self {
+        let mut options = Self {
+            size: None,
+            rules: vec![WinningRule::Rows, WinningRule::Columns],
+            log_path: None,
+            simulations: None,
+            seed: 0,
+        };
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--size" => {
+                    let size = args.next().expect("Missing size for --size");
+                    let (width, height) = size.split_once('x')
+                        .unwrap_or_else(|| panic!("Invalid size, expected <width>x<height>: {0:?}", size));
+
+                    options.size = Some((
+                        width.parse().unwrap_or_else(|_| panic!("Invalid width: {0:?}", width)),
+                        height.parse().unwrap_or_else(|_| panic!("Invalid height: {0:?}", height)),
+                    ));
+                }
+                "--rules" => {
+                    options.rules = args.next().expect("Missing rules for --rules")
+                        .split(',')
+                        .map(WinningRule::from_token)
+                        .collect();
+                }
+                "--log" => options.log_path = Some(args.next().expect("Missing path for --log")),
+                "--simulate" => {
+                    let iterations = args.next().expect("Missing iterations for --simulate");
+                    let iterations = iterations.parse().unwrap_or_else(|_| panic!("Invalid iterations: {0:?}", iterations));
+                    assert!(iterations > 0, "Need at least one iteration to simulate");
+
+                    options.simulations = Some(iterations);
+                }
+                "--seed" => {
+                    let seed = args.next().expect("Missing seed for --seed");
+                    options.seed = seed.parse().unwrap_or_else(|_| panic!("Invalid seed: {0:?}", seed));
+                }
+                _ => panic!("Invalid argument: {0:?}", arg),
+            }
+        }
+
+        options
     }
 }
 
 fn main() {
+    let options = Options::from_args();
+
     let mut lines: VecDeque<String> = stdin().lock().lines()
         .map(|line| line.expect("Cannot read line"))
         .collect();
@@ -109,8 +677,7 @@ fn main() {
     let drawn_numbers = lines.pop_front()
         .expect("Could not read drawn numbers");
 
-    let mut drawn_numbers: VecDeque<u32> = drawn_numbers.split(",")
-        .into_iter()
+    let drawn_numbers: Vec<u32> = drawn_numbers.split(',')
         .map(|token| token.parse::<u32>().unwrap())
         .collect();
 
@@ -122,7 +689,7 @@ fn main() {
         // Skip newline between entries
         lines.pop_front();
 
-        let board = BingoBoard::from_lines(&mut lines);
+        let board = BingoBoard::from_lines(&mut lines, options.size);
 
         // println!("board={0:?}", board);
         boards.push(board);
@@ -130,41 +697,38 @@ fn main() {
 
     // println!("#boards={0:?}", boards.len());
 
-    let mut winning_boards: Vec<(BingoBoard, u32)> = Vec::new();
-    winning_boards.reserve_exact(boards.len());
-
-    while !drawn_numbers.is_empty() {
-        let drawn_number = drawn_numbers.pop_front()
-            .expect("Could not get next drawn number");
-
-        // println!("drawn_number={0:?}", drawn_number);
-
-        let mut index = 0;
-        while index < boards.len() {
-            let board = &mut boards[index];
-            board.mark(drawn_number);
-
-            if board.is_bingo() {
-                let board = boards.remove(index);
-                winning_boards.push((board, drawn_number));
-            } else {
-                index += 1;
-            }
-        }
+    let mut game = BingoGame::new(boards, &options.rules);
+    let log = options.log_path.as_ref()
+        .map(|_path| GameLog::record(&mut game, &drawn_numbers));
+    let wins = match &log {
+        Some(log) => log.wins.clone(),
+        None => game.play(&drawn_numbers),
+    };
+
+    let win = wins.first().expect("No board has won");
+    println!("part1: result={0:?} board={1:?} draw={2:?} number={3:?} pattern={4:?}",
+             win.score, win.board, win.draw, win.number, game.pattern_index.patterns[win.pattern].name);
+
+    let win = wins.last().expect("No board has won");
+    println!("part2: result={0:?} board={1:?} draw={2:?} number={3:?} pattern={4:?}",
+             win.score, win.board, win.draw, win.number, game.pattern_index.patterns[win.pattern].name);
+
+    if let (Some(path), Some(log)) = (&options.log_path, &log) {
+        fs::write(path, log.to_json(&game.pattern_index))
+            .unwrap_or_else(|error| panic!("Cannot write game log to {0:?}: {1}", path, error));
+        println!("log={0:?}", path);
     }
 
-    let board = winning_boards.first().unwrap();
-    let result = determine_result(&board.0, board.1);
-    println!("part1: result={0:?}", result);
-
-    let board = winning_boards.last().unwrap();
-    let result = determine_result(&board.0, board.1);
-    println!("part2: result={0:?}", result);
+    if let Some(iterations) = options.simulations {
+        let statistics = simulate(&mut game, &drawn_numbers, iterations, options.seed);
+        println!("simulation: iterations={0:?} seed={1:?}", iterations, options.seed);
+        print_simulation(&statistics, iterations);
+    }
 }
 
 fn determine_result(board: &BingoBoard, drawn_number: u32) -> u32 {
     let unmarked: Vec<u32> = board.entries.iter()
-        .filter(|entry| entry.marked == false)
+        .filter(|entry| !entry.marked)
         .map(|entry| entry.value)
         .collect();
 
@@ -1,6 +1,7 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs;
 use std::io::{BufRead, stdin};
-use std::ops::RangeInclusive;
 
 #[derive(Debug)]
 struct Line {
@@ -16,14 +17,14 @@ impl Line {
         let mut tokens = tokens.iter();
 
         let pair = tokens.next()
-            .expect(format!("Could not read x1,y1 from {0:?}", input).as_str());
+            .unwrap_or_else(|| panic!("Could not read x1,y1 from {0:?}", input));
 
         let mut pair: VecDeque<&str> = pair.split(',').collect();
         let x1 = Self::pop_as_i32(&mut pair);
         let y1 = Self::pop_as_i32(&mut pair);
 
         let pair = tokens.next()
-            .expect(format!("Could not read x2,y2 from {0:?}", input).as_str());
+            .unwrap_or_else(|| panic!("Could not read x2,y2 from {0:?}", input));
 
         let mut pair: VecDeque<&str> = pair.split(',').collect();
         let x2 = Self::pop_as_i32(&mut pair);
@@ -51,9 +52,73 @@ impl Line {
     fn is_vertical(&self) -> bool {
         self.y1 == self.y2
     }
+
+    // Number of points visited by `for_each_point`
+    fn point_count(&self) -> u64 {
+        let dx = (self.x2 as i64 - self.x1 as i64).unsigned_abs();
+        let dy = (self.y2 as i64 - self.y1 as i64).unsigned_abs();
+
+        dx.max(dy) + 1
+    }
+
+    // via https://en.wikipedia.org/wiki/Bresenham%27s_line_algorithm
+    fn for_each_point<F>(&self, mut visit: F)
+        where F: FnMut(i32, i32)
+    {
+        let (mut x1, mut y1, x2, y2) = (self.x1, self.y1, self.x2, self.y2);
+
+        let dx = (x2 - x1).abs();
+        let sx = if x1 < x2 { 1 } else { -1 };
+
+        let dy = -(y2 - y1).abs();
+        let sy = if y1 < y2 { 1 } else { -1 };
+
+        let mut err = dx + dy;
+
+        // println!("scan: line={0:?} dx={1:?} dy={2:?}", self, dx, dy);
+
+        loop {
+            visit(x1, y1);
+
+            if x1 == x2 && y1 == y2 {
+                break;
+            }
+
+            let e2 = err + err;
+
+            if e2 >= dy {
+                err += dy;
+                x1 += sx;
+            }
+
+            if e2 <= dx {
+                err += dx;
+                y1 += sy;
+            }
+        }
+    }
+
+    fn contains(&self, x: i32, y: i32) -> bool {
+        if let Some(segment) = Segment::from_line(self) {
+            let (a, b) = segment.orientation.normal();
+            let (x, y) = (x as i64, y as i64);
+
+            return a * x + b * y == segment.offset && segment.contains_position(segment.orientation.position(x, y));
+        }
+
+        let mut found = false;
+        self.for_each_point(|px, py| found |= px == x && py == y);
+        found
+    }
 }
 
-#[derive(Debug)]
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{0},{1} -> {2},{3}", self.x1, self.y1, self.x2, self.y2)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 struct Bounds {
     left: i32,
     top: i32,
@@ -62,21 +127,47 @@ struct Bounds {
 }
 
 impl Bounds {
-    fn new() -> Self {
+    fn new(x: i32, y: i32) -> Self {
         Self {
-            left: 0,
-            top: 0,
-            right: 0,
-            bottom: 0,
+            left: x,
+            top: y,
+            right: x,
+            bottom: y,
+        }
+    }
+
+    // Every point covered by a line lies within the bounding box of its endpoints
+    fn from_lines(lines: &[Line]) -> Option<Self> {
+        let first = lines.first()?;
+        let mut bounds = Self::new(first.x1, first.y1);
+
+        for line in lines {
+            bounds.grow_to_contain(line.x1, line.y1);
+            bounds.grow_to_contain(line.x2, line.y2);
         }
+
+        Some(bounds)
+    }
+
+    fn width(&self) -> usize {
+        (self.right as i64 - self.left as i64 + 1) as usize
+    }
+
+    fn height(&self) -> usize {
+        (self.bottom as i64 - self.top as i64 + 1) as usize
     }
 
-    fn width(&self) -> RangeInclusive<usize> {
-        RangeInclusive::new(0, (self.right - self.left) as usize)
+    fn area(&self) -> u64 {
+        self.width() as u64 * self.height() as u64
     }
 
-    fn height(&self) -> RangeInclusive<usize> {
-        RangeInclusive::new(0, (self.bottom - self.top) as usize)
+    fn contains(&self, x: i32, y: i32) -> bool {
+        self.left <= x && x <= self.right && self.top <= y && y <= self.bottom
+    }
+
+    #[inline]
+    fn index(&self, x: i32, y: i32) -> usize {
+        (y - self.top) as usize * self.width() + (x - self.left) as usize
     }
 
     fn grow_to_contain(&mut self, x: i32, y: i32) {
@@ -89,140 +180,865 @@ impl Bounds {
 
 type GridKey = (i32, i32);
 
+// Grids keep their crossings in a HashMap instead when they have more cells than this, or when the lines cover
+// less than one in MAX_DENSE_RATIO of their cells, as with a few lines far apart
+const MAX_DENSE_AREA: u64 = 1 << 22;
+const MAX_DENSE_RATIO: u64 = 16;
+
+#[derive(Debug)]
+enum Crossings {
+    Dense(Vec<i32>),
+    Sparse(HashMap<GridKey, i32>),
+}
+
 #[derive(Debug)]
 struct Grid {
-    crossings: HashMap<GridKey, i32>,
+    bounds: Option<Bounds>,
+    crossings: Crossings,
 }
 
 impl Grid {
-    fn new() -> Self {
+    fn new(lines: &[Line]) -> Self {
+        let bounds = Bounds::from_lines(lines);
+        let covered_points: u64 = lines.iter().map(Line::point_count).sum();
+
+        let crossings = match bounds {
+            Some(bounds) if bounds.area() <= MAX_DENSE_AREA && bounds.area() <= covered_points * MAX_DENSE_RATIO => {
+                Crossings::Dense(vec![0; bounds.area() as usize])
+            }
+            _ => Crossings::Sparse(HashMap::new()),
+        };
+
         Self {
-            crossings: HashMap::new(),
+            bounds,
+            crossings,
         }
     }
 
     fn count_of_at_least_crossings(&self, min_crossings: i32) -> usize {
-        self.crossings.iter()
-            .filter(|(_, &count)| count >= min_crossings)
-            .count()
+        match &self.crossings {
+            Crossings::Dense(counts) => counts.iter()
+                .filter(|&&count| count >= min_crossings)
+                .count(),
+            Crossings::Sparse(counts) => counts.values()
+                .filter(|&&count| count >= min_crossings)
+                .count(),
+        }
+    }
+
+    fn get(&self, x: i32, y: i32) -> i32 {
+        match (&self.crossings, &self.bounds) {
+            (Crossings::Dense(counts), Some(bounds)) if bounds.contains(x, y) => counts[bounds.index(x, y)],
+            (Crossings::Dense(_), _) => 0,
+            (Crossings::Sparse(counts), _) => counts.get(&(x, y)).copied().unwrap_or(0),
+        }
     }
 
     fn increment_at(&mut self, x: i32, y: i32) {
         // println!("incrementing at ({0:?}, {1:?})", x, y);
-        *self.crossings.entry((x, y)).or_insert(0) += 1
+        match &mut self.crossings {
+            Crossings::Dense(counts) => {
+                let bounds = self.bounds.as_ref().expect("Dense grid without bounds");
+                assert!(bounds.contains(x, y), "({0:?}, {1:?}) is outside of {2:?}", x, y, bounds);
+
+                counts[bounds.index(x, y)] += 1
+            }
+            Crossings::Sparse(counts) => *counts.entry((x, y)).or_insert(0) += 1,
+        }
     }
 
-    // via https://en.wikipedia.org/wiki/Bresenham%27s_line_algorithm
     fn bresenham(&mut self, line: &Line) {
-        let (mut x1, mut y1, x2, y2) = (line.x1, line.y1, line.x2, line.y2);
+        line.for_each_point(|x, y| self.increment_at(x, y));
+    }
 
-        let dx = (x2 - x1).abs();
-        let sx = if x1 < x2 { 1 } else { -1 };
+    fn scan_horizontal(&mut self, y: i32, x1: i32, x2: i32) {
+        let (left, right) = if x1 < x2 { (x1, x2) } else { (x2, x1) };
+        // println!("horizontal: y={0:?} left={1:?} right={2:?}", y, left, right);
 
-        let dy = -(y2 - y1).abs();
-        let sy = if y1 < y2 { 1 } else { -1 };
+        for column in left..=right {
+            self.increment_at(column, y);
+        }
+    }
 
-        let mut err = dx + dy;
+    fn scan_vertical(&mut self, x: i32, y1: i32, y2: i32) {
+        let (top, bottom) = if y1 < y2 { (y1, y2) } else { (y2, y1) };
+        // println!("vertical: x={0:?} top={1:?} bottom={2:?}", x, top, bottom);
 
-        // println!("scan: line={0:?} dx={1:?} dy={2:?}", line, dx, dy);
+        for row in top..=bottom {
+            self.increment_at(x, row);
+        }
+    }
 
-        loop {
-            self.increment_at(x1, y1);
+    fn draw(&self) {
+        let Some(bounds) = self.bounds else {
+            return;
+        };
 
-            if x1 == x2 && y1 == y2 {
-                break;
+        // println!("grid={0:?}", self);
+        for y in bounds.top..=bounds.bottom {
+            for x in bounds.left..=bounds.right {
+                match self.get(x, y) {
+                    0 => print!("."),
+                    count => print!("{0}", count),
+                }
             }
+            println!("|");
+        }
+    }
+}
 
-            let e2 = err + err;
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Orientation {
+    Horizontal,
+    Vertical,
+    Diagonal,
+    AntiDiagonal,
+}
 
-            if e2 >= dy {
-                err += dy;
-                x1 += sx;
+impl Orientation {
+    // Normal (a, b) of the lines with this orientation, so that a * x + b * y is constant along them
+    fn normal(self) -> (i64, i64) {
+        match self {
+            Orientation::Horizontal => (0, 1),
+            Orientation::Vertical => (1, 0),
+            Orientation::Diagonal => (1, -1),
+            Orientation::AntiDiagonal => (1, 1),
+        }
+    }
+
+    // Position along a line with this orientation; x except for vertical lines
+    fn position(self, x: i64, y: i64) -> i64 {
+        match self {
+            Orientation::Vertical => y,
+            _ => x,
+        }
+    }
+}
+
+// A line rewritten as the range of positions [start, end] on the line where a * x + b * y == offset
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    orientation: Orientation,
+    offset: i64,
+    start: i64,
+    end: i64,
+}
+
+impl Segment {
+    fn from_line(line: &Line) -> Option<Self> {
+        let (x1, y1, x2, y2) = (line.x1 as i64, line.y1 as i64, line.x2 as i64, line.y2 as i64);
+
+        let orientation = if x1 == x2 {
+            Orientation::Vertical
+        } else if y1 == y2 {
+            Orientation::Horizontal
+        } else if x2 - x1 == y2 - y1 {
+            Orientation::Diagonal
+        } else if x2 - x1 == y1 - y2 {
+            Orientation::AntiDiagonal
+        } else {
+            return None;
+        };
+
+        let (a, b) = orientation.normal();
+        let (p1, p2) = (orientation.position(x1, y1), orientation.position(x2, y2));
+
+        Some(Self {
+            orientation,
+            offset: a * x1 + b * y1,
+            start: p1.min(p2),
+            end: p1.max(p2),
+        })
+    }
+
+    fn min_x(&self) -> i64 {
+        match self.orientation {
+            Orientation::Vertical => self.offset,
+            _ => self.start,
+        }
+    }
+
+    fn max_x(&self) -> i64 {
+        match self.orientation {
+            Orientation::Vertical => self.offset,
+            _ => self.end,
+        }
+    }
+
+    // The single lattice point shared with a segment of another orientation, if any
+    fn crossing(&self, other: &Segment) -> Option<(i64, i64)> {
+        let (a1, b1) = self.orientation.normal();
+        let (a2, b2) = other.orientation.normal();
+
+        let determinant = a1 * b2 - a2 * b1;
+        if determinant == 0 {
+            return None;
+        }
+
+        let x = self.offset * b2 - other.offset * b1;
+        let y = a1 * other.offset - a2 * self.offset;
+
+        if x % determinant != 0 || y % determinant != 0 {
+            return None;
+        }
+
+        let (x, y) = (x / determinant, y / determinant);
+
+        if self.contains_position(self.orientation.position(x, y)) && other.contains_position(other.orientation.position(x, y)) {
+            Some((x, y))
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn contains_position(&self, position: i64) -> bool {
+        self.start <= position && position <= self.end
+    }
+}
+
+// Counts the points covered by at least two lines without visiting every covered point: overlaps of collinear
+// segments are found per line by sweeping over their start and end positions, and crossings of differently
+// oriented segments by sweeping over x while keeping the segments whose x range is still active.
+fn count_overlaps_sweep(lines: &[Line], include_diagonals: bool) -> usize {
+    let segments: Vec<Segment> = lines.iter()
+        .filter(|line| include_diagonals || line.is_horizontal() || line.is_vertical())
+        .map(|line| Segment::from_line(line).unwrap_or_else(|| panic!("Line is neither axis-aligned nor diagonal: {0:?}", line)))
+        .collect();
+
+    // Ranges of positions covered at least twice, merged and sorted per line
+    let mut overlaps: HashMap<(Orientation, i64), Vec<(i64, i64)>> = HashMap::new();
+    {
+        let mut events_by_line: HashMap<(Orientation, i64), Vec<(i64, i32)>> = HashMap::new();
+
+        for segment in &segments {
+            let events = events_by_line.entry((segment.orientation, segment.offset)).or_default();
+            events.push((segment.start, 1));
+            events.push((segment.end + 1, -1));
+        }
+
+        for (key, mut events) in events_by_line {
+            events.sort_unstable();
+
+            let mut ranges: Vec<(i64, i64)> = Vec::new();
+            let mut coverage = 0;
+
+            for (index, &(position, delta)) in events.iter().enumerate() {
+                coverage += delta;
+
+                let next_position = events.get(index + 1).map(|event| event.0);
+
+                if coverage >= 2 && next_position != Some(position) {
+                    let end = next_position.expect("Coverage must drop to 0 at the end of a line") - 1;
+
+                    match ranges.last_mut() {
+                        Some(last) if last.1 + 1 == position => last.1 = end,
+                        _ => ranges.push((position, end)),
+                    }
+                }
             }
 
-            if e2 <= dx {
-                err += dx;
-                y1 += sy;
+            if !ranges.is_empty() {
+                overlaps.insert(key, ranges);
             }
         }
     }
 
-    fn scan_horizontal(&mut self, y: i32, x1: i32, x2: i32) {
-        let (left, right) = if x1 < x2 { (x1, x2) } else { (x2, x1) };
-        // println!("horizontal: y={0:?} left={1:?} right={2:?}", y, left, right);
+    let overlapping_lines_containing = |x: i64, y: i64| {
+        [Orientation::Horizontal, Orientation::Vertical, Orientation::Diagonal, Orientation::AntiDiagonal].iter()
+            .filter(|&&orientation| {
+                let (a, b) = orientation.normal();
+                let position = orientation.position(x, y);
 
-        for column in left..=right {
-            self.increment_at(column, y);
+                overlaps.get(&(orientation, a * x + b * y))
+                    .map(|ranges| {
+                        let index = ranges.partition_point(|range| range.1 < position);
+                        index < ranges.len() && ranges[index].0 <= position
+                    })
+                    .unwrap_or(false)
+            })
+            .count()
+    };
+
+    let mut crossings: HashSet<(i64, i64)> = HashSet::new();
+    {
+        let mut sorted: Vec<&Segment> = segments.iter().collect();
+        sorted.sort_unstable_by_key(|segment| segment.min_x());
+
+        let mut active: Vec<&Segment> = Vec::new();
+
+        for segment in sorted {
+            active.retain(|other| other.max_x() >= segment.min_x());
+
+            for other in &active {
+                if let Some(point) = segment.crossing(other) {
+                    crossings.insert(point);
+                }
+            }
+
+            active.push(segment);
         }
     }
 
-    fn scan_vertical(&mut self, x: i32, y1: i32, y2: i32) {
-        let (top, bottom) = if y1 < y2 { (y1, y2) } else { (y2, y1) };
-        // println!("vertical: x={0:?} top={1:?} bottom={2:?}", x, top, bottom);
+    let mut count: i64 = overlaps.values()
+        .flat_map(|ranges| ranges.iter())
+        .map(|&(start, end)| end - start + 1)
+        .sum();
 
-        for row in top..=bottom {
-            self.increment_at(x, row);
+    // A crossing adds a point unless it already lies in an overlap; if it lies in overlaps of several
+    // orientations it has been counted once per orientation above
+    for &(x, y) in &crossings {
+        count += match overlapping_lines_containing(x, y) {
+            0 => 1,
+            n => 1 - n as i64,
+        };
+    }
+
+    count as usize
+}
+
+// Answers spatial questions about the vent lines, on top of their rasterised crossings
+#[derive(Debug)]
+struct VentIndex<'a> {
+    lines: &'a [Line],
+    grid: Grid,
+    lines_by_offset: HashMap<(Orientation, i64), Vec<usize>>,
+    // Lines that are neither axis-aligned nor diagonal and have to be walked point by point
+    other_lines: Vec<usize>,
+    // Summed-area table of the points with at least two crossings, only for dense grids
+    overlap_sums: Option<Vec<u32>>,
+}
+
+impl<'a> VentIndex<'a> {
+    fn new(lines: &'a [Line]) -> Self {
+        let grid = rasterise(lines, true);
+
+        let mut lines_by_offset: HashMap<(Orientation, i64), Vec<usize>> = HashMap::new();
+        let mut other_lines = Vec::new();
+
+        for (index, line) in lines.iter().enumerate() {
+            match Segment::from_line(line) {
+                Some(segment) => lines_by_offset.entry((segment.orientation, segment.offset)).or_default().push(index),
+                None => other_lines.push(index),
+            }
+        }
+
+        let overlap_sums = match (&grid.crossings, &grid.bounds) {
+            (Crossings::Dense(counts), Some(bounds)) => {
+                let (width, height) = (bounds.width(), bounds.height());
+                let mut sums = vec![0; (width + 1) * (height + 1)];
+
+                for row in 0..height {
+                    for column in 0..width {
+                        let overlap = (counts[row * width + column] >= 2) as u32;
+
+                        sums[(row + 1) * (width + 1) + column + 1] = overlap
+                            + sums[row * (width + 1) + column + 1]
+                            + sums[(row + 1) * (width + 1) + column]
+                            - sums[row * (width + 1) + column];
+                    }
+                }
+
+                Some(sums)
+            }
+            _ => None,
+        };
+
+        Self {
+            lines,
+            grid,
+            lines_by_offset,
+            other_lines,
+            overlap_sums,
         }
     }
 
-    fn draw(&self) {
-        let mut bounds = Bounds::new();
+    fn lines_through(&self, x: i32, y: i32) -> Vec<usize> {
+        let orientations = [Orientation::Horizontal, Orientation::Vertical, Orientation::Diagonal, Orientation::AntiDiagonal];
+
+        let mut result: Vec<usize> = orientations.iter()
+            .flat_map(|&orientation| {
+                let (a, b) = orientation.normal();
+                self.lines_by_offset.get(&(orientation, a * x as i64 + b * y as i64))
+            })
+            .flatten()
+            .chain(self.other_lines.iter())
+            .copied()
+            .filter(|&index| self.lines[index].contains(x, y))
+            .collect();
+
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
+
+    // Number of points with at least two crossings within the inclusive rectangle
+    fn crossings_within(&self, left: i32, top: i32, right: i32, bottom: i32) -> usize {
+        let (left, right) = (left.min(right), left.max(right));
+        let (top, bottom) = (top.min(bottom), top.max(bottom));
+
+        let Some(bounds) = self.grid.bounds else {
+            return 0;
+        };
+
+        let Some(sums) = &self.overlap_sums else {
+            return match &self.grid.crossings {
+                Crossings::Sparse(counts) => counts.iter()
+                    .filter(|&(&(x, y), &count)| count >= 2 && left <= x && x <= right && top <= y && y <= bottom)
+                    .count(),
+                Crossings::Dense(_) => unreachable!("Dense grids always have overlap sums"),
+            };
+        };
+
+        // Clip to the grid, outside of which there are no crossings
+        let (left, top) = (left.max(bounds.left), top.max(bounds.top));
+        let (right, bottom) = (right.min(bounds.right), bottom.min(bounds.bottom));
 
-        for coordinate in self.crossings.keys() {
-            bounds.grow_to_contain(coordinate.0, coordinate.1);
+        if left > right || top > bottom {
+            return 0;
         }
 
-        // println!("grid={0:?}", self);
-        for row in bounds.width() {
-            for column in bounds.height() {
-                let key: GridKey = (column as i32, row as i32);
-                if let Some(count) = self.crossings.get(&key) {
-                    print!("{0}", count);
-                } else {
-                    print!(".");
+        let stride = bounds.width() + 1;
+        let (x1, y1) = ((left - bounds.left) as usize, (top - bounds.top) as usize);
+        let (x2, y2) = ((right - bounds.left) as usize + 1, (bottom - bounds.top) as usize + 1);
+
+        (sums[y2 * stride + x2] + sums[y1 * stride + x1] - sums[y1 * stride + x2] - sums[y2 * stride + x1]) as usize
+    }
+
+    // The point covered by the most lines, preferring the topmost and then leftmost one on ties
+    fn maximum_overlap(&self) -> Option<(GridKey, i32, Vec<usize>)> {
+        let best = match (&self.grid.crossings, &self.grid.bounds) {
+            (Crossings::Dense(counts), Some(bounds)) => counts.iter()
+                .enumerate()
+                .filter(|&(_index, &count)| count > 0)
+                .min_by_key(|&(index, &count)| (-count, index))
+                .map(|(index, &count)| {
+                    let x = bounds.left + (index % bounds.width()) as i32;
+                    let y = bounds.top + (index / bounds.width()) as i32;
+                    ((x, y), count)
+                }),
+            (Crossings::Dense(_), None) => None,
+            (Crossings::Sparse(counts), _) => counts.iter()
+                .min_by_key(|&(&(x, y), &count)| (-count, y, x))
+                .map(|(&key, &count)| (key, count)),
+        };
+
+        best.map(|((x, y), count)| ((x, y), count, self.lines_through(x, y)))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Query {
+    Point(i32, i32),
+    Rectangle(i32, i32, i32, i32),
+    Maximum,
+}
+
+impl Query {
+    // Format: `point:<x>,<y>`, `rect:<x1>,<y1>,<x2>,<y2>` or `max`
+    fn from_token(token: &str) -> Self {
+        let coordinates = |values: &str, expected: usize| -> Vec<i32> {
+            let values: Vec<i32> = values.split(',')
+                .map(|value| value.parse().unwrap_or_else(|_| panic!("Invalid coordinate {0:?} in {1:?}", value, token)))
+                .collect();
+            assert_eq!(values.len(), expected, "Invalid number of coordinates in {0:?}", token);
+            values
+        };
+
+        if token == "max" {
+            Query::Maximum
+        } else if let Some(values) = token.strip_prefix("point:") {
+            let values = coordinates(values, 2);
+            Query::Point(values[0], values[1])
+        } else if let Some(values) = token.strip_prefix("rect:") {
+            let values = coordinates(values, 4);
+            Query::Rectangle(values[0], values[1], values[2], values[3])
+        } else {
+            panic!("Invalid query: {0:?}", token)
+        }
+    }
+
+    fn answer(&self, index: &VentIndex) {
+        let describe = |lines: &[usize]| lines.iter()
+            .map(|&line| format!("#{0} {1}", line, index.lines[line]))
+            .collect::<Vec<String>>()
+            .join("; ");
+
+        match *self {
+            Query::Point(x, y) => {
+                let lines = index.lines_through(x, y);
+                println!("query: point=({0:?}, {1:?}) count={2:?} lines=[{3}]", x, y, lines.len(), describe(&lines));
+            }
+            Query::Rectangle(x1, y1, x2, y2) => {
+                let crossings = index.crossings_within(x1, y1, x2, y2);
+                println!("query: rect=({0:?}, {1:?})..=({2:?}, {3:?}) crossings={4:?}", x1, y1, x2, y2, crossings);
+            }
+            Query::Maximum => match index.maximum_overlap() {
+                Some(((x, y), count, lines)) => {
+                    println!("query: max=({0:?}, {1:?}) count={2:?} lines=[{3}]", x, y, count, describe(&lines));
+                }
+                None => println!("query: max=None"),
+            },
+        }
+    }
+}
+
+type Color = [u8; 3];
+
+const OVERLAY_COLOR: Color = [0, 255, 255];
+
+// Black for no crossings, then through purple, red and yellow to white for the maximum count
+const HEAT_SCALE: [Color; 5] = [[0, 0, 0], [87, 16, 110], [188, 55, 84], [249, 142, 9], [252, 255, 164]];
+
+// Images with more pixels than this are refused. Encoding a PNG holds about four copies of the 3 bytes per pixel,
+// so this keeps it to some 200 MB, e.g. a 4096x4096 image.
+const MAX_IMAGE_PIXELS: u64 = 1 << 24;
+
+#[derive(Debug)]
+struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Image {
+    fn heat_color(count: i32, max_count: i32) -> Color {
+        if count <= 0 || max_count <= 0 {
+            return HEAT_SCALE[0];
+        }
+
+        // Count 1 starts just above black so that single lines remain visible
+        let t = if max_count == 1 { 1.0 } else { 0.25 + 0.75 * (count - 1) as f64 / (max_count - 1) as f64 };
+        let scaled = t * (HEAT_SCALE.len() - 1) as f64;
+        let index = (scaled.floor() as usize).min(HEAT_SCALE.len() - 2);
+        let fraction = scaled - index as f64;
+
+        let (from, to) = (HEAT_SCALE[index], HEAT_SCALE[index + 1]);
+        let mix = |channel: usize| (from[channel] as f64 + (to[channel] as f64 - from[channel] as f64) * fraction).round() as u8;
+
+        [mix(0), mix(1), mix(2)]
+    }
+
+    // Every grid cell becomes a `scale` x `scale` block; the overlay lines run through the centers of their cells
+    fn heatmap(grid: &Grid, overlay: &[&Line], scale: usize) -> Self {
+        let Some(bounds) = grid.bounds else {
+            return Self { width: 0, height: 0, pixels: Vec::new() };
+        };
+
+        let (width, height) = (bounds.width() * scale, bounds.height() * scale);
+        assert!((width as u64) * (height as u64) <= MAX_IMAGE_PIXELS, "Heatmap of {0}x{1} pixels is too large", width, height);
+
+        let max_count = (bounds.top..=bounds.bottom)
+            .flat_map(|y| (bounds.left..=bounds.right).map(move |x| (x, y)))
+            .map(|(x, y)| grid.get(x, y))
+            .max()
+            .unwrap_or(0);
+
+        let mut pixels = vec![HEAT_SCALE[0]; width * height];
+
+        for y in bounds.top..=bounds.bottom {
+            for x in bounds.left..=bounds.right {
+                let color = Self::heat_color(grid.get(x, y), max_count);
+                let (column, row) = ((x - bounds.left) as usize * scale, (y - bounds.top) as usize * scale);
+
+                for dy in 0..scale {
+                    pixels[(row + dy) * width + column..(row + dy) * width + column + scale].fill(color);
                 }
             }
-            println!("|");
         }
+
+        let to_pixel = |value: i32, origin: i32| (value - origin) * scale as i32 + scale as i32 / 2;
+
+        for line in overlay {
+            let scaled = Line {
+                x1: to_pixel(line.x1, bounds.left),
+                y1: to_pixel(line.y1, bounds.top),
+                x2: to_pixel(line.x2, bounds.left),
+                y2: to_pixel(line.y2, bounds.top),
+            };
+
+            // Blend rather than paint over, so that the heat below a line stays visible at scale 1
+            scaled.for_each_point(|x, y| {
+                let pixel = &mut pixels[y as usize * width + x as usize];
+                *pixel = [0, 1, 2].map(|channel| ((pixel[channel] as u16 + OVERLAY_COLOR[channel] as u16) / 2) as u8);
+            });
+        }
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    fn to_pgm(&self) -> Vec<u8> {
+        let mut data = format!("P5\n{0} {1}\n255\n", self.width, self.height).into_bytes();
+
+        // Rec. 601 luma
+        data.extend(self.pixels.iter()
+            .map(|&[r, g, b]| ((299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000) as u8));
+
+        data
     }
+
+    fn to_ppm(&self) -> Vec<u8> {
+        let mut data = format!("P6\n{0} {1}\n255\n", self.width, self.height).into_bytes();
+        data.extend(self.pixels.iter().flatten());
+        data
+    }
+
+    // 8-bit RGB without compression: the image data is stored in uncompressed deflate blocks
+    fn to_png(&self) -> Vec<u8> {
+        let mut raw = Vec::with_capacity(self.height * (self.width * 3 + 1));
+
+        for row in self.pixels.chunks(self.width.max(1)).take(self.height) {
+            // Filter type None
+            raw.push(0);
+            raw.extend(row.iter().flatten());
+        }
+
+        let mut zlib = vec![0x78, 0x01];
+        let mut blocks = raw.chunks(u16::MAX as usize).peekable();
+
+        if blocks.peek().is_none() {
+            zlib.extend([1, 0, 0, 0xff, 0xff]);
+        }
+
+        while let Some(block) = blocks.next() {
+            let length = block.len() as u16;
+
+            zlib.push(blocks.peek().is_none() as u8);
+            zlib.extend(length.to_le_bytes());
+            zlib.extend((!length).to_le_bytes());
+            zlib.extend(block);
+        }
+
+        zlib.extend(adler32(&raw).to_be_bytes());
+
+        let mut header = Vec::new();
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // Bit depth 8, color type RGB, default compression, filtering and no interlacing
+        header.extend([8, 2, 0, 0, 0]);
+
+        let mut data = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+        write_png_chunk(&mut data, b"IHDR", &header);
+        write_png_chunk(&mut data, b"IDAT", &zlib);
+        write_png_chunk(&mut data, b"IEND", &[]);
+
+        data
+    }
+}
+
+fn write_png_chunk(data: &mut Vec<u8>, kind: &[u8; 4], content: &[u8]) {
+    data.extend((content.len() as u32).to_be_bytes());
+
+    let start = data.len();
+    data.extend(kind);
+    data.extend(content);
+
+    let crc = crc32(&data[start..]);
+    data.extend(crc.to_be_bytes());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in bytes {
+        crc ^= byte as u32;
+
+        for _bit in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+fn export_heatmap(lines: &[Line], options: &Options, path: &str) {
+    let include_diagonals = options.heatmap_diagonals;
+    let grid = rasterise(lines, include_diagonals);
+
+    let overlay: Vec<&Line> = if options.heatmap_overlay {
+        lines.iter()
+            .filter(|line| include_diagonals || line.is_horizontal() || line.is_vertical())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let image = Image::heatmap(&grid, &overlay, options.heatmap_scale);
+
+    let data = match path.rsplit('.').next() {
+        Some("pgm") => {
+            assert!(overlay.is_empty(), "Line overlays need a color format such as .ppm or .png");
+            image.to_pgm()
+        }
+        Some("ppm") => image.to_ppm(),
+        Some("png") => image.to_png(),
+        _ => panic!("Unsupported heatmap format, expected .pgm, .ppm or .png: {0:?}", path),
+    };
+
+    fs::write(path, data).unwrap_or_else(|error| panic!("Cannot write heatmap to {0:?}: {1}", path, error));
+    println!("heatmap={0:?} width={1:?} height={2:?}", path, image.width, image.height);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Algorithm {
+    Rasterise,
+    Sweep,
+}
+
+#[derive(Debug)]
+struct Options {
+    algorithm: Algorithm,
+    verify: bool,
+    draw: bool,
+    queries: Vec<Query>,
+    heatmap_path: Option<String>,
+    heatmap_diagonals: bool,
+    heatmap_overlay: bool,
+    heatmap_scale: usize,
+}
+
+impl Options {
+    fn from_args() -> Self {
+        let mut options = Self {
+            algorithm: Algorithm::Rasterise,
+            verify: false,
+            draw: false,
+            queries: Vec::new(),
+            heatmap_path: None,
+            heatmap_diagonals: true,
+            heatmap_overlay: false,
+            heatmap_scale: 1,
+        };
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--algorithm" => {
+                    options.algorithm = match args.next().expect("Missing algorithm for --algorithm").as_str() {
+                        "rasterise" => Algorithm::Rasterise,
+                        "sweep" => Algorithm::Sweep,
+                        algorithm => panic!("Invalid algorithm: {0:?}", algorithm),
+                    }
+                }
+                "--verify" => options.verify = true,
+                "--draw" => options.draw = true,
+                "--heatmap" => options.heatmap_path = Some(args.next().expect("Missing path for --heatmap")),
+                "--heatmap-lines" => {
+                    options.heatmap_diagonals = match args.next().expect("Missing lines for --heatmap-lines").as_str() {
+                        "axis-aligned" => false,
+                        "all" => true,
+                        lines => panic!("Invalid heatmap lines: {0:?}", lines),
+                    }
+                }
+                "--heatmap-overlay" => options.heatmap_overlay = true,
+                "--heatmap-scale" => {
+                    let scale = args.next().expect("Missing scale for --heatmap-scale");
+                    options.heatmap_scale = scale.parse().unwrap_or_else(|_| panic!("Invalid scale: {0:?}", scale));
+                    assert!(options.heatmap_scale > 0, "Heatmap scale must be positive");
+                }
+                "--query" => options.queries.push(Query::from_token(&args.next().expect("Missing query for --query"))),
+                _ => panic!("Invalid argument: {0:?}", arg),
+            }
+        }
+
+        options
+    }
+}
+
+fn rasterise(lines: &[Line], include_diagonals: bool) -> Grid {
+    let mut grid = Grid::new(lines);
+
+    for line in lines {
+        if line.is_horizontal() {
+            grid.scan_vertical(line.x1, line.y1, line.y2);
+        } else if line.is_vertical() {
+            grid.scan_horizontal(line.y1, line.x1, line.x2);
+        } else if include_diagonals {
+            grid.bresenham(line);
+        }
+    }
+
+    grid
+}
+
+fn count_overlaps_rasterise(lines: &[Line], include_diagonals: bool, draw: bool) -> usize {
+    let grid = rasterise(lines, include_diagonals);
+
+    if draw {
+        grid.draw();
+    }
+
+    grid.count_of_at_least_crossings(2)
+}
+
+fn count_overlaps(lines: &[Line], include_diagonals: bool, options: &Options) -> usize {
+    let count = match options.algorithm {
+        Algorithm::Rasterise => count_overlaps_rasterise(lines, include_diagonals, options.draw),
+        Algorithm::Sweep => count_overlaps_sweep(lines, include_diagonals),
+    };
+
+    if options.verify {
+        let expected = match options.algorithm {
+            Algorithm::Rasterise => count_overlaps_sweep(lines, include_diagonals),
+            Algorithm::Sweep => count_overlaps_rasterise(lines, include_diagonals, false),
+        };
+
+        assert_eq!(count, expected, "Sweep line and rasteriser disagree");
+    }
+
+    count
 }
 
 fn main() {
+    let options = Options::from_args();
+
     let lines: Vec<Line> = stdin().lock().lines()
         .map(|line| line.expect("Cannot read line"))
-        .map(|line| Line::from_string(line))
+        .map(Line::from_string)
         .collect();
 
     // println!("lines={0:?}", lines);
 
-    {
-        let mut grid = Grid::new();
+    // Other vent files have no puzzle answers, so the extra modes are run on their own
+    if options.heatmap_path.is_some() || !options.queries.is_empty() {
+        if let Some(path) = &options.heatmap_path {
+            export_heatmap(&lines, &options, path);
+        }
+
+        if !options.queries.is_empty() {
+            let index = VentIndex::new(&lines);
 
-        for line in &lines {
-            if line.is_horizontal() {
-                grid.scan_vertical(line.x1, line.y1, line.y2);
-            } else if line.is_vertical() {
-                grid.scan_horizontal(line.y1, line.x1, line.x2);
+            for query in &options.queries {
+                query.answer(&index);
             }
         }
 
-        // grid.draw();
+        return;
+    }
 
-        let at_least_two_crossings = grid.count_of_at_least_crossings(2);
+    {
+        let at_least_two_crossings = count_overlaps(&lines, false, &options);
 
         println!("part1: result={0:?}", at_least_two_crossings);
         assert_eq!(at_least_two_crossings, 8111);
     }
     {
-        let mut grid = Grid::new();
-
-        for line in &lines {
-            grid.bresenham(line);
-        }
-
-        // grid.draw();
-
-        let at_least_two_crossings = grid.count_of_at_least_crossings(2);
+        let at_least_two_crossings = count_overlaps(&lines, true, &options);
 
         println!("part2: result={0:?}", at_least_two_crossings);
         assert_eq!(at_least_two_crossings, 22088);
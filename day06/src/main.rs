@@ -1,14 +1,691 @@
+use std::fmt;
+use std::fs;
 use std::io::{BufRead, stdin};
 
+#[derive(Clone, Debug)]
+struct Species {
+    name: String,
+    // Timer value at which a fish spawns
+    spawn_age: usize,
+    // Timer value of a fish right after it has spawned
+    reset_age: usize,
+    // Timer value of a newborn fish
+    newborn_age: usize,
+}
+
+impl Species {
+    fn lanternfish() -> Self {
+        Self {
+            name: "lanternfish".to_owned(),
+            spawn_age: 0,
+            reset_age: 6,
+            newborn_age: 8,
+        }
+    }
+
+    // Format: `<name>[,spawn=<age>][,reset=<age>][,newborn=<age>][,maturation=<days>]`, starting from lanternfish biology.
+    // The maturation delay is the number of extra days a newborn needs before its first cycle.
+    fn from_token(token: &str) -> Self {
+        let mut tokens = token.split(',');
+        let mut species = Self::lanternfish();
+
+        species.name = tokens.next().expect("Could not determine species name").to_owned();
+
+        let mut maturation_delay = None;
+
+        for parameter in tokens {
+            let (key, value) = parameter.split_once('=')
+                .unwrap_or_else(|| panic!("Invalid species parameter: {0:?}", parameter));
+            let value: usize = value.parse()
+                .unwrap_or_else(|_| panic!("Invalid value for {0:?}: {1:?}", key, value));
+
+            match key {
+                "spawn" => species.spawn_age = value,
+                "reset" => species.reset_age = value,
+                "newborn" => species.newborn_age = value,
+                "maturation" => maturation_delay = Some(value),
+                _ => panic!("Invalid species parameter: {0:?}", key),
+            }
+        }
+
+        if let Some(delay) = maturation_delay {
+            species.newborn_age = species.reset_age + delay;
+        }
+
+        assert!(species.reset_age >= species.spawn_age && species.newborn_age >= species.spawn_age,
+                "Fish of {0:?} must not be reset or born below their spawn age", species.name);
+
+        species
+    }
+
+    fn max_age(&self) -> usize {
+        self.reset_age.max(self.newborn_age)
+    }
+
+    // Entry (i, j) is the number of fish with timer i tomorrow per fish with timer j today
+    fn transition<A: Arithmetic>(&self, arithmetic: &A) -> Matrix<A::Value> {
+        let size = self.max_age() + 1;
+        let mut transition = vec![0u64; size * size];
+
+        for age in 0..size {
+            if age > self.spawn_age {
+                transition[(age - 1) * size + age] += 1;
+            } else if age < self.spawn_age {
+                transition[age * size + age] += 1;
+            }
+        }
+
+        transition[self.reset_age * size + self.spawn_age] += 1;
+        transition[self.newborn_age * size + self.spawn_age] += 1;
+
+        Matrix {
+            size,
+            values: transition.iter().map(|&value| arithmetic.value(value)).collect(),
+        }
+    }
+
+    // Asymptotic factor by which the population grows per day, i.e. the dominant eigenvalue of the transition.
+    // Power iteration runs on the transition plus identity, which has the same dominant eigenvector but does not
+    // oscillate for periodic lifecycles.
+    fn growth_rate(&self) -> f64 {
+        let transition = self.transition(&Float);
+        let size = transition.size;
+
+        let mut shifted = transition.clone();
+        for i in 0..size {
+            shifted.values[i * size + i] += 1.0;
+        }
+
+        let mut vector = vec![1.0; size];
+        let mut rate = 0.0;
+
+        for _iteration in 0..10_000 {
+            let next = shifted.apply(&Float, &vector);
+            let sum: f64 = next.iter().sum();
+            let next_rate = sum / vector.iter().sum::<f64>() - 1.0;
+
+            vector = next.iter().map(|value| value / sum).collect();
+
+            if (next_rate - rate).abs() < 1e-15 {
+                return next_rate;
+            }
+
+            rate = next_rate;
+        }
+
+        rate
+    }
+
+    // Number of fish after `days` days that started out as a single fish with each timer value, itself included
+    fn descendants(&self, days: u64) -> Vec<BigUint> {
+        let power = self.transition(&Exact).pow(&Exact, days);
+        let size = power.size;
+
+        (0..size)
+            .map(|age| (0..size).fold(BigUint::default(), |sum, row| sum.add(&power.values[row * size + age])))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+struct DayStatistics<T> {
+    day: usize,
+    total: T,
+    // Number of fish per timer value, per species
+    histograms: Vec<Vec<T>>,
+}
+
+// Fish are only tracked by species and timer, so each day costs O(species * ages) independent of their number
+#[derive(Clone, Debug)]
+struct PopulationModel {
+    species: Vec<Species>,
+    initial: Vec<Vec<u64>>,
+}
+
+impl PopulationModel {
+    fn new(species: Vec<Species>, fish: &[Vec<usize>]) -> Self {
+        assert_eq!(species.len(), fish.len(), "Need initial fish for every species");
+
+        let initial = species.iter()
+            .zip(fish)
+            .map(|(species, fish)| {
+                let mut histogram = vec![0; species.max_age() + 1];
+
+                for &age in fish {
+                    assert!(age >= species.spawn_age && age < histogram.len(),
+                            "Age {0:?} is out of range for {1:?}", age, species.name);
+                    histogram[age] += 1;
+                }
+
+                histogram
+            })
+            .collect();
+
+        Self {
+            species,
+            initial,
+        }
+    }
+
+    // Starts with the initial population on day 0
+    fn days<'a, A: Arithmetic>(&'a self, arithmetic: &'a A) -> Days<'a, A> {
+        Days {
+            model: self,
+            arithmetic,
+            day: 0,
+            histograms: self.initial.iter()
+                .map(|histogram| histogram.iter().map(|&count| arithmetic.value(count)).collect())
+                .collect(),
+        }
+    }
+
+    fn step<A: Arithmetic>(arithmetic: &A, species: &Species, histogram: &mut [A::Value]) {
+        let spawning_fish = histogram[species.spawn_age].clone();
+
+        histogram[species.spawn_age..].rotate_left(1);
+        histogram[species.max_age()] = arithmetic.value(0);
+
+        histogram[species.reset_age] = arithmetic.add(&histogram[species.reset_age], &spawning_fish);
+        histogram[species.newborn_age] = arithmetic.add(&histogram[species.newborn_age], &spawning_fish);
+    }
+
+    // Total population after `days` days in O(ages^3 * log(days)) per species
+    fn solve<A: Arithmetic>(&self, arithmetic: &A, days: u64) -> A::Value {
+        self.species.iter()
+            .zip(&self.initial)
+            .flat_map(|(species, histogram)| {
+                let initial: Vec<A::Value> = histogram.iter().map(|&count| arithmetic.value(count)).collect();
+
+                species.transition(arithmetic)
+                    .pow(arithmetic, days)
+                    .apply(arithmetic, &initial)
+            })
+            .fold(arithmetic.value(0), |total, count| arithmetic.add(&total, &count))
+    }
+
+    // The series are computed exactly, as populations quickly outgrow u64 over longer spans
+    fn series_csv(&self, days: usize) -> String {
+        let mut header = vec!["day".to_owned(), "total".to_owned()];
+
+        for species in &self.species {
+            header.extend((0..=species.max_age()).map(|age| format!("{0}_{1}", species.name, age)));
+        }
+
+        let mut csv = header.join(",") + "\n";
+
+        for day in self.days(&Exact).take(days + 1) {
+            let mut row = vec![day.day.to_string(), day.total.to_string()];
+            row.extend(day.histograms.iter().flatten().map(|count| count.to_string()));
+
+            csv += &(row.join(",") + "\n");
+        }
+
+        csv
+    }
+
+    fn series_json(&self, days: usize) -> String {
+        let species = self.species.iter()
+            .map(|species| json_string(&species.name))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        let days = self.days(&Exact).take(days + 1)
+            .map(|day| {
+                let histograms = self.species.iter()
+                    .zip(&day.histograms)
+                    .map(|(species, histogram)| format!("{0}: {1}", json_string(&species.name), format_histogram(histogram)))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+
+                format!("    {{\"day\": {0}, \"total\": {1}, \"histograms\": {{{2}}}}}", day.day, day.total, histograms)
+            })
+            .collect::<Vec<String>>()
+            .join(",\n");
+
+        format!("{{\n  \"species\": [{0}],\n  \"days\": [\n{1}\n  ]\n}}\n", species, days)
+    }
+}
+
+#[derive(Debug)]
+struct Days<'a, A: Arithmetic> {
+    model: &'a PopulationModel,
+    arithmetic: &'a A,
+    day: usize,
+    histograms: Vec<Vec<A::Value>>,
+}
+
+impl<'a, A: Arithmetic> Iterator for Days<'a, A> {
+    type Item = DayStatistics<A::Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let total = self.histograms.iter()
+            .flatten()
+            .fold(self.arithmetic.value(0), |total, count| self.arithmetic.add(&total, count));
+
+        let statistics = DayStatistics {
+            day: self.day,
+            total,
+            histograms: self.histograms.clone(),
+        };
+
+        for (species, histogram) in self.model.species.iter().zip(&mut self.histograms) {
+            PopulationModel::step(self.arithmetic, species, histogram);
+        }
+        self.day += 1;
+
+        Some(statistics)
+    }
+}
+
+// Non-negative arbitrary-precision integer, as little-endian base 2^32 limbs without trailing zeroes
+#[derive(Clone, Debug, Default, PartialEq)]
+struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    fn from_u64(value: u64) -> Self {
+        let mut result = Self { limbs: vec![value as u32, (value >> 32) as u32] };
+        result.normalize();
+        result
+    }
+
+    fn normalize(&mut self) {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        let mut limbs = Vec::with_capacity(self.limbs.len().max(other.limbs.len()) + 1);
+        let mut carry = 0u64;
+
+        for index in 0..self.limbs.len().max(other.limbs.len()) {
+            let sum = *self.limbs.get(index).unwrap_or(&0) as u64 + *other.limbs.get(index).unwrap_or(&0) as u64 + carry;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+
+        limbs.push(carry as u32);
+
+        let mut result = Self { limbs };
+        result.normalize();
+        result
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        if self.limbs.is_empty() || other.limbs.is_empty() {
+            return Self::default();
+        }
+
+        let mut limbs = vec![0u32; self.limbs.len() + other.limbs.len()];
+
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let product = a as u64 * b as u64 + limbs[i + j] as u64 + carry;
+                limbs[i + j] = product as u32;
+                carry = product >> 32;
+            }
+
+            limbs[i + other.limbs.len()] = carry as u32;
+        }
+
+        let mut result = Self { limbs };
+        result.normalize();
+        result
+    }
+
+    // Divides in place and returns the remainder
+    fn div_rem_small(&mut self, divisor: u32) -> u32 {
+        let mut remainder = 0u64;
+
+        for limb in self.limbs.iter_mut().rev() {
+            let value = (remainder << 32) | *limb as u64;
+            *limb = (value / divisor as u64) as u32;
+            remainder = value % divisor as u64;
+        }
+
+        self.normalize();
+        remainder as u32
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.limbs.is_empty() {
+            return write!(f, "0");
+        }
+
+        let mut value = self.clone();
+        let mut chunks = Vec::new();
+
+        while !value.limbs.is_empty() {
+            chunks.push(value.div_rem_small(1_000_000_000));
+        }
+
+        write!(f, "{0}", chunks.last().unwrap())?;
+
+        for chunk in chunks.iter().rev().skip(1) {
+            write!(f, "{0:09}", chunk)?;
+        }
+
+        Ok(())
+    }
+}
+
+// The number type a solution is computed in, either exact or modulo some prime
+trait Arithmetic {
+    type Value: Clone + fmt::Display;
+
+    fn value(&self, value: u64) -> Self::Value;
+
+    fn add(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+
+    fn mul(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+}
+
+#[derive(Debug)]
+struct Exact;
+
+impl Arithmetic for Exact {
+    type Value = BigUint;
+
+    fn value(&self, value: u64) -> BigUint {
+        BigUint::from_u64(value)
+    }
+
+    fn add(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        a.add(b)
+    }
+
+    fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        a.mul(b)
+    }
+}
+
+// Plain u64 arithmetic that panics instead of wrapping around
+#[derive(Debug)]
+struct Checked;
+
+impl Arithmetic for Checked {
+    type Value = u64;
+
+    fn value(&self, value: u64) -> u64 {
+        value
+    }
+
+    fn add(&self, a: &u64, b: &u64) -> u64 {
+        a.checked_add(*b).expect("Population overflows")
+    }
+
+    fn mul(&self, a: &u64, b: &u64) -> u64 {
+        a.checked_mul(*b).expect("Population overflows")
+    }
+}
+
+#[derive(Debug)]
+struct Modular {
+    modulus: u64,
+}
+
+impl Arithmetic for Modular {
+    type Value = u64;
+
+    fn value(&self, value: u64) -> u64 {
+        value % self.modulus
+    }
+
+    fn add(&self, a: &u64, b: &u64) -> u64 {
+        ((*a as u128 + *b as u128) % self.modulus as u128) as u64
+    }
+
+    fn mul(&self, a: &u64, b: &u64) -> u64 {
+        ((*a as u128 * *b as u128) % self.modulus as u128) as u64
+    }
+}
+
+#[derive(Debug)]
+struct Float;
+
+impl Arithmetic for Float {
+    type Value = f64;
+
+    fn value(&self, value: u64) -> f64 {
+        value as f64
+    }
+
+    fn add(&self, a: &f64, b: &f64) -> f64 {
+        a + b
+    }
+
+    fn mul(&self, a: &f64, b: &f64) -> f64 {
+        a * b
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Matrix<T> {
+    size: usize,
+    // Row-major
+    values: Vec<T>,
+}
+
+impl<T: Clone> Matrix<T> {
+    fn identity<A: Arithmetic<Value = T>>(arithmetic: &A, size: usize) -> Self {
+        let mut values = vec![arithmetic.value(0); size * size];
+
+        for i in 0..size {
+            values[i * size + i] = arithmetic.value(1);
+        }
+
+        Self { size, values }
+    }
+
+    fn mul<A: Arithmetic<Value = T>>(&self, arithmetic: &A, other: &Self) -> Self {
+        let size = self.size;
+        let mut values = vec![arithmetic.value(0); size * size];
+
+        for i in 0..size {
+            for k in 0..size {
+                let a = &self.values[i * size + k];
+
+                for j in 0..size {
+                    let product = arithmetic.mul(a, &other.values[k * size + j]);
+                    values[i * size + j] = arithmetic.add(&values[i * size + j], &product);
+                }
+            }
+        }
+
+        Self { size, values }
+    }
+
+    fn pow<A: Arithmetic<Value = T>>(&self, arithmetic: &A, mut exponent: u64) -> Self {
+        let mut result = Self::identity(arithmetic, self.size);
+        let mut base = self.clone();
+
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.mul(arithmetic, &base);
+            }
+
+            exponent >>= 1;
+
+            if exponent > 0 {
+                base = base.mul(arithmetic, &base);
+            }
+        }
+
+        result
+    }
+
+    fn apply<A: Arithmetic<Value = T>>(&self, arithmetic: &A, vector: &[T]) -> Vec<T> {
+        (0..self.size)
+            .map(|i| (0..self.size).fold(arithmetic.value(0), |sum, j| {
+                arithmetic.add(&sum, &arithmetic.mul(&self.values[i * self.size + j], &vector[j]))
+            }))
+            .collect()
+    }
+}
+
+// Formats like the Debug output of a Vec, which BigUint does not have
+fn format_histogram(histogram: &[BigUint]) -> String {
+    let counts = histogram.iter()
+        .map(|count| count.to_string())
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    format!("[{0}]", counts)
+}
+
+fn json_string(value: &str) -> String {
+    let mut result = String::from("\"");
+
+    for c in value.chars() {
+        match c {
+            '"' => result += "\\\"",
+            '\\' => result += "\\\\",
+            c if c.is_control() => result += &format!("\\u{0:04x}", c as u32),
+            c => result.push(c),
+        }
+    }
+
+    result.push('"');
+    result
+}
+
+// Days exported by --series when no --days are given
+const DEFAULT_DAYS: usize = 256;
+
+#[derive(Debug, Default)]
+struct Options {
+    species: Vec<Species>,
+    days: Option<usize>,
+    series_path: Option<String>,
+    analyse: bool,
+    solve_days: Option<u64>,
+    modulus: Option<u64>,
+}
+
+impl Options {
+    fn from_args() -> Self {
+        let mut options = Self::default();
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--species" => options.species.push(Species::from_token(&args.next().expect("Missing species for --species"))),
+                "--days" => {
+                    let days = args.next().expect("Missing number of days for --days");
+                    options.days = Some(days.parse().unwrap_or_else(|_| panic!("Invalid number of days: {0:?}", days)));
+                }
+                "--series" => options.series_path = Some(args.next().expect("Missing path for --series")),
+                "--analyse" => options.analyse = true,
+                "--solve" => {
+                    let days = args.next().expect("Missing number of days for --solve");
+                    options.solve_days = Some(days.parse().unwrap_or_else(|_| panic!("Invalid number of days: {0:?}", days)));
+                }
+                "--modulo" => {
+                    let modulus = args.next().expect("Missing modulus for --modulo");
+                    let modulus = modulus.parse().unwrap_or_else(|_| panic!("Invalid modulus: {0:?}", modulus));
+                    assert!(modulus > 1, "Modulus must be greater than 1");
+
+                    options.modulus = Some(modulus);
+                }
+                _ => panic!("Invalid argument: {0:?}", arg),
+            }
+        }
+
+        options
+    }
+}
+
+// Everything besides the puzzle parts, on all species given with --species
+fn run_extra_modes(options: &Options, fish_by_species: &[Vec<usize>]) {
+    let species = if options.species.is_empty() { vec![Species::lanternfish()] } else { options.species.clone() };
+    let model = PopulationModel::new(species, fish_by_species);
+
+    if let Some(days) = options.solve_days {
+        match options.modulus {
+            Some(modulus) => {
+                let number_of_fish = model.solve(&Modular { modulus }, days);
+                println!("solve: days={0:?} modulus={1:?} number of fish={2}", days, modulus, number_of_fish);
+            }
+            None => {
+                let number_of_fish = model.solve(&Exact, days);
+                println!("solve: days={0:?} number of fish={1}", days, number_of_fish);
+            }
+        }
+    }
+
+    if let Some(days) = options.days {
+        for day in model.days(&Exact).take(days + 1) {
+            let histograms = model.species.iter()
+                .zip(&day.histograms)
+                .map(|(species, histogram)| format!("{0}={1}", species.name, format_histogram(histogram)))
+                .collect::<Vec<String>>()
+                .join(" ");
+
+            println!("day={0:?} total={1} {2}", day.day, day.total, histograms);
+        }
+    }
+
+    let days = options.days.unwrap_or(DEFAULT_DAYS);
+
+    if let Some(path) = &options.series_path {
+        let data = match path.rsplit('.').next() {
+            Some("csv") => model.series_csv(days),
+            Some("json") => model.series_json(days),
+            _ => panic!("Unsupported series format, expected .csv or .json: {0:?}", path),
+        };
+
+        fs::write(path, data).unwrap_or_else(|error| panic!("Cannot write series to {0:?}: {1}", path, error));
+        println!("series={0:?} days={1:?}", path, days);
+    }
+
+    if options.analyse {
+        for (species, histogram) in model.species.iter().zip(&model.initial) {
+            println!("analysis: species={0:?} growth_rate={1:.12} doubling_days={2:.3}",
+                     species.name, species.growth_rate(), 2f64.ln() / species.growth_rate().ln());
+
+            for (age, descendants) in species.descendants(days as u64).iter().enumerate().skip(species.spawn_age) {
+                let contribution = descendants.mul(&BigUint::from_u64(histogram[age]));
+                println!("  age={0:?} fish={1:?} descendants_per_fish={2} contribution={3}",
+                         age, histogram[age], descendants, contribution);
+            }
+        }
+    }
+}
+
 fn main() {
+    let options = Options::from_args();
+
     let lines: Vec<String> = stdin().lock().lines()
         .map(|line| line.expect("Cannot read line"))
         .collect();
 
-    let fish: Vec<i32> = lines.iter().next()
+    // Every line holds the fish of one species, in the order of the --species arguments
+    let fish_by_species: Vec<Vec<usize>> = lines.iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.split(',')
+            .map(|token| token.trim().parse::<usize>().unwrap())
+            .collect())
+        .collect();
+
+    // Other species and populations are not the puzzle, so there are no answers to check them against
+    let is_extra = !options.species.is_empty() || options.days.is_some() || options.series_path.is_some()
+        || options.analyse || options.solve_days.is_some();
+
+    if is_extra {
+        run_extra_modes(&options, &fish_by_species);
+        return;
+    }
+
+    let fish: Vec<i32> = fish_by_species.first()
         .expect("Could not read fish")
-        .split(',')
-        .map(|token| token.parse::<i32>().unwrap())
+        .iter()
+        .map(|&age| age as i32)
         .collect();
 
     {
@@ -40,25 +717,12 @@ fn main() {
     }
 
     {
-        let mut fish_age = fish.clone().into_iter()
-            .fold(vec![0; 9], |mut ages, value| {
-                ages[value as usize] += 1;
-                ages
-            });
-
-        println!("fish_age={0:?}", fish_age);
+        let model = PopulationModel::new(vec![Species::lanternfish()], &fish_by_species[..1]);
+        println!("fish_age={0:?}", model.initial[0]);
 
-        for _day in 0..256 {
-            let spawning_fish = fish_age[0];
-
-            // Fish with age 0 will spawn a new fish with age 8 today
-            fish_age.rotate_left(1);
-
-            // They are also respawned with age 6
-            fish_age[6] += spawning_fish;
-        }
+        let day = model.days(&Checked).nth(256).unwrap();
 
-        let number_of_fish: usize = fish_age.iter().sum();
+        let number_of_fish = day.total;
         println!("part2: number of fish={0:?}", number_of_fish);
         assert_eq!(number_of_fish, 1708791884591);
     }
@@ -1,42 +1,522 @@
+use std::fmt::Write as _;
+use std::fs;
 use std::io::{BufRead, stdin};
 
+#[derive(Clone, Debug, PartialEq)]
+enum CostModel {
+    Linear,
+    Triangular,
+    Quadratic,
+    // Linear up to the cap, constant beyond
+    Capped(i64),
+    // Breakpoints (distance, cost) sorted by distance, interpolated linearly and extrapolated with the last slope
+    PiecewiseLinear(Vec<(i64, i64)>),
+}
+
+impl CostModel {
+    // Format: `linear`, `triangular`, `quadratic`, `capped <cap>` or `piecewise <distance>:<cost> ...`
+    fn from_tokens(tokens: &[&str]) -> Self {
+        let parse = |token: &str| token.parse::<i64>().unwrap_or_else(|_| panic!("Invalid number: {0:?}", token));
+
+        match tokens {
+            ["linear"] => CostModel::Linear,
+            ["triangular"] => CostModel::Triangular,
+            ["quadratic"] => CostModel::Quadratic,
+            ["capped", cap] => CostModel::Capped(parse(cap)),
+            ["piecewise", points @ ..] => {
+                let mut points: Vec<(i64, i64)> = points.iter()
+                    .map(|point| {
+                        let (distance, cost) = point.split_once(':')
+                            .unwrap_or_else(|| panic!("Invalid breakpoint, expected <distance>:<cost>: {0:?}", point));
+                        (parse(distance), parse(cost))
+                    })
+                    .collect();
+                points.sort_unstable();
+
+                assert!(points.len() >= 2, "Piecewise-linear costs need at least two breakpoints");
+                assert!(points.windows(2).all(|pair| pair[0].0 < pair[1].0), "Breakpoints must have distinct distances");
+
+                CostModel::PiecewiseLinear(points)
+            }
+            _ => panic!("Invalid cost model: {0:?}", tokens.join(" ")),
+        }
+    }
+
+    fn cost(&self, distance: i64) -> i64 {
+        let overflow = || panic!("Fuel cost for distance {0:?} overflows", distance);
+
+        match self {
+            CostModel::Linear => distance,
+            CostModel::Triangular => distance.checked_mul(distance + 1).unwrap_or_else(overflow) / 2,
+            CostModel::Quadratic => distance.checked_mul(distance).unwrap_or_else(overflow),
+            CostModel::Capped(cap) => distance.min(*cap),
+            CostModel::PiecewiseLinear(points) => {
+                let segment = points.windows(2)
+                    .position(|pair| distance <= pair[1].0)
+                    .unwrap_or(points.len() - 2);
+                let ((x1, y1), (x2, y2)) = (points[segment], points[segment + 1]);
+
+                (distance - x1).checked_mul(y2 - y1)
+                    .and_then(|rise| (rise / (x2 - x1)).checked_add(y1))
+                    .unwrap_or_else(overflow)
+            }
+        }
+    }
+
+    // Whether a longer distance never costs less
+    fn is_non_decreasing(&self) -> bool {
+        match self {
+            CostModel::PiecewiseLinear(points) => points.windows(2).all(|pair| pair[0].1 <= pair[1].1),
+            _ => true,
+        }
+    }
+
+    // Whether the cost grows convexly with the distance, which makes the total cost convex in the height
+    fn is_convex(&self) -> bool {
+        match self {
+            CostModel::Linear | CostModel::Triangular | CostModel::Quadratic => true,
+            CostModel::Capped(_) => false,
+            // Non-decreasing slopes and a non-decreasing start, as the cost is mirrored for negative offsets.
+            // The slopes also have to be integers: otherwise the interpolated costs are rounded down into steps,
+            // which are not convex.
+            CostModel::PiecewiseLinear(points) => {
+                let slopes: Option<Vec<i64>> = points.windows(2)
+                    .map(|pair| {
+                        let (rise, run) = (pair[1].1 - pair[0].1, pair[1].0 - pair[0].0);
+                        (rise % run == 0).then_some(rise / run)
+                    })
+                    .collect();
+
+                slopes.is_some_and(|slopes| {
+                    points[0].0 <= 0 && slopes[0] >= 0 && slopes.windows(2).all(|pair| pair[0] <= pair[1])
+                })
+            }
+        }
+    }
+}
+
+// Named cost models: the built-in ones plus those defined in a config file
+#[derive(Debug)]
+struct CostModelRegistry {
+    models: Vec<(String, CostModel)>,
+}
+
+impl CostModelRegistry {
+    fn new() -> Self {
+        Self {
+            models: vec![
+                ("linear".to_owned(), CostModel::Linear),
+                ("triangular".to_owned(), CostModel::Triangular),
+                ("quadratic".to_owned(), CostModel::Quadratic),
+            ],
+        }
+    }
+
+    // Format: one `<name> <cost model>` per line, e.g. `steep piecewise 0:0 10:10 20:40`
+    fn load(&mut self, path: &str) {
+        let config = fs::read_to_string(path)
+            .unwrap_or_else(|error| panic!("Cannot read cost models from {0:?}: {1}", path, error));
+
+        for line in config.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let model = CostModel::from_tokens(&tokens[1..]);
+
+            self.models.retain(|(name, _model)| name != tokens[0]);
+            self.models.push((tokens[0].to_owned(), model));
+        }
+    }
+
+    // Besides registered names, parameterised models can be given inline, e.g. `capped:10`
+    fn get(&self, name: &str) -> CostModel {
+        if let Some((_name, model)) = self.models.iter().find(|(model_name, _model)| model_name == name) {
+            return model.clone();
+        }
+
+        let tokens: Vec<&str> = name.split(':').collect();
+        match tokens.as_slice() {
+            ["capped", _cap] => CostModel::from_tokens(&tokens),
+            _ => panic!("Unknown cost model {0:?}, known are: {1}", name,
+                        self.models.iter().map(|(name, _model)| name.as_str()).collect::<Vec<&str>>().join(", ")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Crab {
+    position: i64,
+    weight: i64,
+}
+
+impl Crab {
+    // Format: `<position>` or `<position>*<weight>`, the weight defaults to 1
+    fn from_token(token: &str) -> Self {
+        let parse = |value: &str| value.trim().parse::<i64>().unwrap_or_else(|_| panic!("Invalid crab: {0:?}", token));
+
+        let (position, weight) = match token.split_once('*') {
+            Some((position, weight)) => (parse(position), parse(weight)),
+            None => (parse(token), 1),
+        };
+        assert!(weight > 0, "Crab weights must be positive: {0:?}", token);
+
+        Self {
+            position,
+            weight,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Objective {
+    // Minimise the total fuel cost over all crabs
+    Median,
+    // Minimise the highest fuel cost of any single crab
+    Center,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Solver {
+    // Median for linear, mean for triangular and ternary search for other convex costs
+    Specialised,
+    Ternary,
+    BruteForce,
+}
+
+#[derive(Debug)]
+struct Options {
+    solver: Solver,
+    verify: bool,
+    cost: Option<String>,
+    cost_models_path: Option<String>,
+    curve_path: Option<String>,
+    targets: Option<usize>,
+    objective: Objective,
+}
+
+impl Options {
+    fn from_args() -> Self {
+        let mut options = Self {
+            solver: Solver::Specialised,
+            verify: false,
+            cost: None,
+            cost_models_path: None,
+            curve_path: None,
+            targets: None,
+            objective: Objective::Median,
+        };
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--solver" => {
+                    options.solver = match args.next().expect("Missing solver for --solver").as_str() {
+                        "specialised" => Solver::Specialised,
+                        "ternary" => Solver::Ternary,
+                        "brute-force" => Solver::BruteForce,
+                        solver => panic!("Invalid solver: {0:?}", solver),
+                    }
+                }
+                "--verify" => options.verify = true,
+                "--cost" => options.cost = Some(args.next().expect("Missing cost model for --cost")),
+                "--cost-models" => options.cost_models_path = Some(args.next().expect("Missing path for --cost-models")),
+                "--curve" => options.curve_path = Some(args.next().expect("Missing path for --curve")),
+                "--targets" => {
+                    let targets = args.next().expect("Missing count for --targets");
+                    options.targets = Some(targets.parse().unwrap_or_else(|_| panic!("Invalid target count: {0:?}", targets)));
+                }
+                "--objective" => {
+                    options.objective = match args.next().expect("Missing objective for --objective").as_str() {
+                        "median" => Objective::Median,
+                        "center" => Objective::Center,
+                        objective => panic!("Invalid objective: {0:?}", objective),
+                    }
+                }
+                _ => panic!("Invalid argument: {0:?}", arg),
+            }
+        }
+
+        options
+    }
+}
+
+fn solve(crabs: &[Crab], model: &CostModel, options: &Options) -> (i64, i64) {
+    let optim_fn = |lhs: &i64, rhs: &i64| model.cost((lhs - rhs).abs());
+    let (target_height, global_difference) = determine_optimum(crabs, model, options.solver);
+
+    if options.verify {
+        verify_optimum(crabs, optim_fn, global_difference);
+    }
+
+    (target_height, global_difference)
+}
+
+fn determine_optimum(crabs: &[Crab], model: &CostModel, solver: Solver) -> (i64, i64) {
+    let optim_fn = |lhs: &i64, rhs: &i64| model.cost((lhs - rhs).abs());
+
+    match (solver, model) {
+        (Solver::Specialised, CostModel::Linear) => determine_linear_optimum(crabs),
+        (Solver::Specialised, CostModel::Triangular) => determine_triangular_optimum(crabs),
+        (Solver::Specialised, model) if model.is_convex() => determine_convex_optimum(crabs, optim_fn),
+        (Solver::Ternary, model) => {
+            assert!(model.is_convex(), "Ternary search needs a convex cost model: {0:?}", model);
+            determine_convex_optimum(crabs, optim_fn)
+        }
+        _ => determine_global_optimum(crabs, optim_fn),
+    }
+}
+
+#[derive(Debug)]
+struct Alignment {
+    targets: Vec<i64>,
+    // Index into `targets` for every crab, in input order
+    assignment: Vec<usize>,
+    result: i64,
+}
+
+// As the cost does not decrease with the distance, every crab can go to its nearest target, so the crabs of each
+// target form a run of consecutive positions. Dynamic programming over where these runs start finds the exact
+// optimum, solving every run as a single-target problem.
+fn align(crabs: &[Crab], k: usize, model: &CostModel, options: &Options) -> Alignment {
+    assert!(k > 0, "At least one target is needed");
+    assert!(model.is_non_decreasing(), "Aligning on several targets needs costs that grow with the distance: {0:?}", model);
+
+    // Crabs at the same position always share a target, so they are merged into one: their costs add up for the
+    // median, while only the heaviest one matters for the center
+    let mut positions: Vec<Crab> = Vec::new();
+    let mut sorted = crabs.to_vec();
+    sorted.sort_unstable_by_key(|crab| crab.position);
+
+    for crab in sorted {
+        match positions.last_mut() {
+            Some(last) if last.position == crab.position => match options.objective {
+                Objective::Median => last.weight += crab.weight,
+                Objective::Center => last.weight = last.weight.max(crab.weight),
+            },
+            _ => positions.push(crab),
+        }
+    }
+
+    let count = positions.len();
+    let k = k.min(count);
+
+    // Single-target optimum of the run positions[start..end], computed on first use
+    let mut runs: Vec<Vec<Option<(i64, i64)>>> = vec![vec![None; count + 1]; count];
+    let mut run = |start: usize, end: usize| *runs[start][end].get_or_insert_with(|| match options.objective {
+        Objective::Median => determine_optimum(&positions[start..end], model, options.solver),
+        Objective::Center => determine_center_optimum(&positions[start..end], model, options.solver),
+    });
+
+    // best[targets][end] is the lowest result for positions[..end] with that many targets, and where its last run starts
+    let mut best = vec![vec![(i64::MAX, 0); count + 1]; k + 1];
+    best[0][0] = (0, 0);
+
+    for targets in 1..=k {
+        for end in targets..=count {
+            // A single run has to start at the first position
+            let starts = if targets == 1 { 0..1 } else { targets - 1..end };
+
+            best[targets][end] = match options.objective {
+                Objective::Median => starts
+                    .map(|start| {
+                        let cost = best[targets - 1][start].0.checked_add(run(start, end).1).expect("Total fuel cost overflows");
+                        (cost, start)
+                    })
+                    .min()
+                    .unwrap(),
+                // The result of the earlier runs only grows with the start of the last run, while the cost of the last run
+                // only shrinks, so the best start is where the two cross
+                Objective::Center => {
+                    let (mut lower, mut upper) = (starts.start, starts.end - 1);
+
+                    while lower < upper {
+                        let middle = (lower + upper) / 2;
+
+                        if best[targets - 1][middle].0 >= run(middle, end).1 {
+                            upper = middle;
+                        } else {
+                            lower = middle + 1;
+                        }
+                    }
+
+                    (lower.saturating_sub(1).max(starts.start)..=lower)
+                        .map(|start| (best[targets - 1][start].0.max(run(start, end).1), start))
+                        .min()
+                        .unwrap()
+                }
+            };
+        }
+    }
+
+    let mut boundaries = Vec::new();
+    let mut end = count;
+
+    for targets in (1..=k).rev() {
+        let start = best[targets][end].1;
+        boundaries.push((start, end));
+        end = start;
+    }
+    boundaries.reverse();
+
+    let targets: Vec<i64> = boundaries.iter()
+        .map(|&(start, end)| run(start, end).0)
+        .collect();
+
+    let assignment = crabs.iter()
+        .map(|crab| {
+            let index = positions.binary_search_by_key(&crab.position, |position| position.position).unwrap();
+            boundaries.iter().position(|&(start, end)| (start..end).contains(&index)).unwrap()
+        })
+        .collect();
+
+    Alignment {
+        targets,
+        assignment,
+        result: best[k][count].0,
+    }
+}
+
+fn print_alignment(crabs: &[Crab], alignment: &Alignment, objective: Objective) {
+    println!("targets: k={0:?} objective={1:?} result={2:?}", alignment.targets.len(), objective, alignment.result);
+
+    for (index, target) in alignment.targets.iter().enumerate() {
+        let (count, weight) = crabs.iter()
+            .zip(&alignment.assignment)
+            .filter(|&(_crab, &assigned)| assigned == index)
+            .fold((0, 0), |(count, weight), (crab, _assigned)| (count + 1, weight + crab.weight));
+        println!("  target={0:?} height={1:?} crabs={2:?} weight={3:?}", index, target, count, weight);
+    }
+
+    let assignment = alignment.assignment.iter()
+        .map(|target| target.to_string())
+        .collect::<Vec<String>>()
+        .join(",");
+    println!("  assignment=[{0}]", assignment);
+}
+
+fn cost_curve_csv(crabs: &[Crab], model: &CostModel) -> String {
+    let lower_bound = crabs.iter().map(|crab| crab.position).min().unwrap();
+    let upper_bound = crabs.iter().map(|crab| crab.position).max().unwrap();
+    let optim_fn = |lhs: &i64, rhs: &i64| model.cost((lhs - rhs).abs());
+
+    let mut csv = String::from("height,cost\n");
+
+    for height in lower_bound..=upper_bound {
+        writeln!(csv, "{0},{1}", height, total_cost(crabs, height, &optim_fn)).unwrap();
+    }
+
+    csv
+}
+
 fn main() {
+    let options = Options::from_args();
+
     let lines: Vec<String> = stdin().lock().lines()
         .map(|line| line.expect("Cannot read line"))
         .collect();
 
-    let line = lines.iter().next()
+    let line = lines.first()
         .expect("Could not read input line");
 
-    let positions: Vec<i32> = line.split(',')
-        .map(|token| token.parse::<i32>().unwrap())
+    let crabs: Vec<Crab> = line.split(',')
+        .map(Crab::from_token)
         .collect();
 
-    // println!("positions:{0:?}", positions);
+    // println!("crabs:{0:?}", crabs);
 
-    let (target_height, global_difference) = determine_global_optimum(&positions, |lhs, rhs| (lhs - rhs).abs());
+    let mut registry = CostModelRegistry::new();
+
+    if let Some(path) = &options.cost_models_path {
+        registry.load(path);
+    }
+
+    // Weighted crabs and the extra modes are not the puzzle, so there are no answers to check them against
+    let is_puzzle = crabs.iter().all(|crab| crab.weight == 1)
+        && options.cost.is_none() && options.targets.is_none() && options.curve_path.is_none();
+
+    let (target_height, global_difference) = solve(&crabs, &CostModel::Linear, &options);
     println!("part1: target_height={0:?} result={1:?}", target_height, global_difference);
-    assert_eq!(global_difference, 355764);
 
-    let (target_height, global_difference) = determine_global_optimum(&positions, |lhs, rhs| {
-        let upper_bound = (lhs - rhs).abs();
-        (upper_bound * (upper_bound + 1)) / 2
-    });
+    if is_puzzle {
+        assert_eq!(global_difference, 355764);
+    }
+
+    let (target_height, global_difference) = solve(&crabs, &CostModel::Triangular, &options);
     println!("part2: target_height={0:?} result={1:?}", target_height, global_difference);
-    assert_eq!(global_difference, 99634572);
+
+    if is_puzzle {
+        assert_eq!(global_difference, 99634572);
+    }
+
+    let cost = options.cost.as_deref().unwrap_or("linear");
+    let model = registry.get(cost);
+
+    if options.cost.is_some() {
+        let (target_height, global_difference) = solve(&crabs, &model, &options);
+        println!("cost: model={0:?} target_height={1:?} result={2:?}", cost, target_height, global_difference);
+    }
+
+    if let Some(k) = options.targets {
+        let alignment = align(&crabs, k, &model, &options);
+        print_alignment(&crabs, &alignment, options.objective);
+    }
+
+    if let Some(path) = &options.curve_path {
+        fs::write(path, cost_curve_csv(&crabs, &model))
+            .unwrap_or_else(|error| panic!("Cannot write cost curve to {0:?}: {1}", path, error));
+        println!("curve={0:?} model={1:?}", path, cost);
+    }
+}
+
+#[inline]
+fn crab_cost(crab: &Crab, height: i64, optim_fn: &impl Fn(&i64, &i64) -> i64) -> i64 {
+    optim_fn(&crab.position, &height).checked_mul(crab.weight)
+        .unwrap_or_else(|| panic!("Fuel cost of {0:?} at height {1:?} overflows", crab, height))
+}
+
+fn total_cost(crabs: &[Crab], height: i64, optim_fn: &impl Fn(&i64, &i64) -> i64) -> i64 {
+    crabs.iter()
+        .try_fold(0i64, |sum, crab| sum.checked_add(crab_cost(crab, height, optim_fn)))
+        .unwrap_or_else(|| panic!("Total fuel cost at height {0:?} overflows", height))
+}
+
+fn max_cost(crabs: &[Crab], height: i64, optim_fn: &impl Fn(&i64, &i64) -> i64) -> i64 {
+    crabs.iter()
+        .map(|crab| crab_cost(crab, height, optim_fn))
+        .max()
+        .unwrap()
+}
+
+// Several heights may share the optimal cost, so only the costs are compared
+fn verify_optimum(crabs: &[Crab], optim_fn: impl Fn(&i64, &i64) -> i64, difference: i64) {
+    let (_target_height, expected) = determine_global_optimum(crabs, optim_fn);
+    assert_eq!(difference, expected, "Solver disagrees with brute force");
+}
+
+fn determine_global_optimum(crabs: &[Crab], optim_fn: impl Fn(&i64, &i64) -> i64) -> (i64, i64) {
+    determine_optimum_by(crabs, |height| total_cost(crabs, height, &optim_fn))
+}
+
+// Single-target k-center: the height that minimises the highest cost of any crab. The highest of convex costs is
+// convex as well, so the same solvers apply.
+fn determine_center_optimum(crabs: &[Crab], model: &CostModel, solver: Solver) -> (i64, i64) {
+    let optim_fn = |lhs: &i64, rhs: &i64| model.cost((lhs - rhs).abs());
+    let cost_fn = |height| max_cost(crabs, height, &optim_fn);
+
+    match solver {
+        Solver::Specialised | Solver::Ternary if model.is_convex() => determine_convex_optimum_by(crabs, cost_fn),
+        Solver::Ternary => panic!("Ternary search needs a convex cost model: {0:?}", model),
+        _ => determine_optimum_by(crabs, cost_fn),
+    }
 }
 
-fn determine_global_optimum(positions: &Vec<i32>, optim_fn: impl Fn(&i32, &i32) -> i32) -> (i32, i32) {
+fn determine_optimum_by(crabs: &[Crab], cost_fn: impl Fn(i64) -> i64) -> (i64, i64) {
     let mut target_height = -1;
-    let mut global_difference = i32::MAX;
+    let mut global_difference = i64::MAX;
 
-    let lower_bound = positions.iter().min().unwrap().clone();
-    let upper_bound = positions.iter().max().unwrap().clone();
+    let lower_bound = crabs.iter().map(|crab| crab.position).min().unwrap();
+    let upper_bound = crabs.iter().map(|crab| crab.position).max().unwrap();
 
     for current_height in lower_bound..=upper_bound {
-        let current_difference = positions.iter()
-            .map(|position| optim_fn(&position, &current_height))
-            .sum();
+        let current_difference = cost_fn(current_height);
 
         if current_difference < global_difference {
             global_difference = current_difference;
@@ -46,3 +526,65 @@ fn determine_global_optimum(positions: &Vec<i32>, optim_fn: impl Fn(&i32, &i32)
 
     (target_height, global_difference)
 }
+
+// The weighted sum of absolute differences is minimal at the weighted median
+fn determine_linear_optimum(crabs: &[Crab]) -> (i64, i64) {
+    let mut sorted = crabs.to_vec();
+    sorted.sort_unstable_by_key(|crab| crab.position);
+
+    let total_weight: i64 = crabs.iter().map(|crab| crab.weight).sum();
+    let mut cumulative = 0;
+    let median = sorted.iter()
+        .find(|crab| {
+            cumulative += crab.weight;
+            2 * cumulative >= total_weight
+        })
+        .unwrap()
+        .position;
+
+    (median, total_cost(crabs, median, &|lhs: &i64, rhs: &i64| CostModel::Linear.cost((lhs - rhs).abs())))
+}
+
+// The triangular cost is n * (n + 1) / 2, whose optimum lies within 1/2 of the weighted mean
+fn determine_triangular_optimum(crabs: &[Crab]) -> (i64, i64) {
+    let sum: i128 = crabs.iter().map(|crab| crab.position as i128 * crab.weight as i128).sum();
+    let total_weight: i128 = crabs.iter().map(|crab| crab.weight as i128).sum();
+    let mean = sum.div_euclid(total_weight) as i64;
+
+    (mean - 1..=mean + 1)
+        .map(|height| (height, total_cost(crabs, height, &|lhs: &i64, rhs: &i64| CostModel::Triangular.cost((lhs - rhs).abs()))))
+        .min_by_key(|&(height, cost)| (cost, height))
+        .unwrap()
+}
+
+fn determine_convex_optimum(crabs: &[Crab], optim_fn: impl Fn(&i64, &i64) -> i64) -> (i64, i64) {
+    determine_convex_optimum_by(crabs, |height| total_cost(crabs, height, &optim_fn))
+}
+
+// Ternary search over the heights, which finds the optimum of any cost function that is convex in the height
+fn determine_convex_optimum_by(crabs: &[Crab], cost_fn: impl Fn(i64) -> i64) -> (i64, i64) {
+    let mut lower_bound = crabs.iter().map(|crab| crab.position).min().unwrap();
+    let mut upper_bound = crabs.iter().map(|crab| crab.position).max().unwrap();
+
+    while upper_bound - lower_bound > 2 {
+        let third = (upper_bound - lower_bound) / 3;
+        let (left, right) = (lower_bound + third, upper_bound - third);
+
+        let left_cost = cost_fn(left);
+        let right_cost = cost_fn(right);
+
+        if left_cost < right_cost {
+            upper_bound = right - 1;
+        } else if left_cost > right_cost {
+            lower_bound = left + 1;
+        } else {
+            lower_bound = left;
+            upper_bound = right;
+        }
+    }
+
+    (lower_bound..=upper_bound)
+        .map(|height| (height, cost_fn(height)))
+        .min_by_key(|&(height, cost)| (cost, height))
+        .unwrap()
+}
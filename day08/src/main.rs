@@ -1,136 +1,441 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
 use std::io::{BufRead, stdin};
-use std::str::FromStr;
 
-#[derive(Debug)]
-struct SevenSegmentDisplay {
-    digits: BTreeMap<i32, BTreeSet<char>>,
-    output: Vec<i32>,
+// One bit per segment (or per wire), `a` being the lowest
+type Segments = u32;
+
+const MAX_SEGMENTS: usize = 26;
+
+// Canonical segments of the digits 0 to 9
+const DIGIT_GLYPHS: [(&str, &str); 10] = [
+    ("0", "abcefg"), ("1", "cf"), ("2", "acdeg"), ("3", "acdfg"), ("4", "bcdf"),
+    ("5", "abdfg"), ("6", "abdefg"), ("7", "acf"), ("8", "abcdefg"), ("9", "abcdfg"),
+];
+
+// Stop collecting wirings once there are more than this, and report the count as `5040+`.
+// Seven segments only have 7! = 5040 wirings in total, so just the larger alphabets are ever cut short.
+const MAX_WIRINGS: usize = 5040;
+
+// The symbols a display can show and which of its segments light up for each
+#[derive(Clone, Debug)]
+struct Alphabet {
+    segment_count: usize,
+    symbols: Vec<String>,
+    glyphs: Vec<Segments>,
 }
 
-impl SevenSegmentDisplay {
-    fn from_line(line: &String) -> Self {
-        let mut input_output = line.split('|');
+impl Alphabet {
+    fn new(segment_count: usize, table: &[(&str, &str)]) -> Self {
+        assert!(segment_count > 0 && segment_count <= MAX_SEGMENTS, "Unsupported segment count: {0:?}", segment_count);
 
-        let display_digits = input_output.next().expect("Could not determine display digits");
-        let mut digits = BTreeMap::new();
+        let mut alphabet = Self {
+            segment_count,
+            symbols: Vec::new(),
+            glyphs: Vec::new(),
+        };
 
-        let mut len_5: Vec<BTreeSet<char>> = Vec::new();
-        let mut len_6: Vec<BTreeSet<char>> = Vec::new();
+        for &(symbol, pattern) in table {
+            let glyph = alphabet.parse_segments(pattern);
+            assert!(!alphabet.glyphs.contains(&glyph), "Glyph of {0:?} is not unique: {1:?}", symbol, pattern);
+            assert!(!alphabet.symbols.iter().any(|known| known == symbol), "Duplicate symbol: {0:?}", symbol);
 
-        for display_digit in display_digits.split(' ') {
-            // println!("display_digit={0:?}", display_digit);
-            match display_digit.len() {
-                2 => {
-                    digits.insert(1, BTreeSet::from_iter(display_digit.chars()));
-                }
-                3 => {
-                    digits.insert(7, BTreeSet::from_iter(display_digit.chars()));
-                }
-                4 => {
-                    digits.insert(4, BTreeSet::from_iter(display_digit.chars()));
-                }
-                5 => {
-                    len_5.push(BTreeSet::from_iter(display_digit.chars()));
-                }
-                6 => {
-                    len_6.push(BTreeSet::from_iter(display_digit.chars()));
+            alphabet.symbols.push(symbol.to_owned());
+            alphabet.glyphs.push(glyph);
+        }
+
+        alphabet
+    }
+
+    fn digits() -> Self {
+        Self::new(7, &DIGIT_GLYPHS)
+    }
+
+    // Format: one `<symbol> <segments>` per line, e.g. `7 acf`, and optionally `segments <count>`;
+    // without it the count is the highest segment used
+    fn from_file(path: &str) -> Self {
+        let config = fs::read_to_string(path)
+            .unwrap_or_else(|error| panic!("Cannot read alphabet from {0:?}: {1}", path, error));
+
+        let mut segment_count = None;
+        let mut table = Vec::new();
+
+        for line in config.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            match line.split_whitespace().collect::<Vec<&str>>().as_slice() {
+                ["segments", count] => {
+                    segment_count = Some(count.parse().unwrap_or_else(|_| panic!("Invalid segment count: {0:?}", count)));
                 }
-                7 => {
-                    digits.insert(8, BTreeSet::from_iter(display_digit.chars()));
+                [symbol, pattern] => table.push((*symbol, *pattern)),
+                _ => panic!("Invalid glyph definition: {0:?}", line),
+            }
+        }
+
+        let segment_count = segment_count.unwrap_or_else(|| {
+            table.iter()
+                .flat_map(|(_symbol, pattern)| pattern.bytes())
+                .map(|segment| segment.saturating_sub(b'a') as usize + 1)
+                .max()
+                .expect("Alphabet defines no glyphs")
+        });
+
+        Self::new(segment_count, &table)
+    }
+
+    fn parse_segments(&self, pattern: &str) -> Segments {
+        self.try_parse_segments(pattern)
+            .unwrap_or_else(|| panic!("Invalid segment pattern: {0:?}", pattern))
+    }
+
+    fn try_parse_segments(&self, pattern: &str) -> Option<Segments> {
+        pattern.chars()
+            .map(|c| match c {
+                'a'..='z' if ((c as u8 - b'a') as usize) < self.segment_count => Some(1 << (c as u8 - b'a')),
+                _ => None,
+            })
+            .try_fold(0, |segments, segment| Some(segments | segment?))
+    }
+
+    fn symbol_of(&self, segments: Segments) -> Option<usize> {
+        self.glyphs.iter().position(|&glyph| glyph == segments)
+    }
+
+    fn format_segments(segments: Segments) -> String {
+        (0..MAX_SEGMENTS)
+            .filter(|segment| segments & (1 << segment) != 0)
+            .map(|segment| (b'a' + segment as u8) as char)
+            .collect()
+    }
+
+    fn all_segments(&self) -> Segments {
+        Segments::MAX >> (Segments::BITS as usize - self.segment_count)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Wiring {
+    // The segment every wire is connected to
+    Unique(Vec<usize>),
+    Ambiguous(Vec<Vec<usize>>),
+    Inconsistent,
+}
+
+// Finds the wire-to-segment permutations under which every observed pattern lights up a known glyph
+#[derive(Debug)]
+struct WiringSolver<'a> {
+    alphabet: &'a Alphabet,
+    patterns: Vec<Segments>,
+}
+
+impl<'a> WiringSolver<'a> {
+    fn new(alphabet: &'a Alphabet, patterns: &[Segments]) -> Self {
+        let mut patterns = patterns.to_vec();
+        patterns.sort_unstable();
+        patterns.dedup();
+
+        Self {
+            alphabet,
+            patterns,
+        }
+    }
+
+    fn solve(&self) -> Wiring {
+        let candidates = self.initial_candidates();
+
+        let mut wirings = Vec::new();
+        let mut wiring = vec![usize::MAX; self.alphabet.segment_count];
+        self.search(0, 0, &candidates, &mut wiring, &mut wirings);
+
+        match wirings.len() {
+            0 => Wiring::Inconsistent,
+            1 => Wiring::Unique(wirings.pop().unwrap()),
+            _ => Wiring::Ambiguous(wirings),
+        }
+    }
+
+    // A wire lit in a pattern of n segments can only drive a segment of some glyph with n segments,
+    // and an unlit wire cannot drive a segment that all those glyphs share
+    fn initial_candidates(&self) -> Vec<Segments> {
+        let all_segments = self.alphabet.all_segments();
+        let mut candidates = vec![all_segments; self.alphabet.segment_count];
+
+        for &pattern in &self.patterns {
+            let same_size = self.alphabet.glyphs.iter()
+                .filter(|glyph| glyph.count_ones() == pattern.count_ones());
+            let union = same_size.clone().fold(0, |union, glyph| union | glyph);
+            let intersection = same_size.fold(all_segments, |intersection, glyph| intersection & glyph);
+
+            for (wire, candidate) in candidates.iter_mut().enumerate() {
+                if pattern & (1 << wire) != 0 {
+                    *candidate &= union;
+                } else {
+                    *candidate &= !intersection;
                 }
-                _ => (),
             }
         }
 
-        // Known: 1, 4, 7, 8
-        {
-            // 1 is completely contained in 3 but not in 2 or 5
-            let one = digits.get(&1).unwrap();
-            let three = len_5.iter()
-                .enumerate()
-                .filter(|&(_index, digit)| Self::contains_all(digit, one))
-                .nth(0)
-                .expect("Could not determine 3");
-
-            digits.insert(3, len_5.remove(three.0));
-        }
-        // Known: 1, 3, 4, 7, 8
-        {
-            // 3 is completely contained in 9 but not in 0 or 6
-            let three = digits.get(&3).unwrap();
-            let nine = len_6.iter()
-                .enumerate()
-                .filter(|&(_index, digit)| Self::contains_all(digit, three))
-                .nth(0)
-                .expect("Could not determine 9");
-
-            digits.insert(9, len_6.remove(nine.0));
-        }
-        // Known: 1, 3, 4, 7, 8, 9
-        {
-            // 1 is completely contained in 0 but not in 6
-            let one = digits.get(&1).unwrap();
-            let zero = len_6.iter()
-                .enumerate()
-                .filter(|&(_index, digit)| Self::contains_all(digit, one))
-                .nth(0)
-                .expect("Could not determine 0");
-
-            digits.insert(0, len_6.remove(zero.0));
-            digits.insert(6, len_6.pop().expect("Could not determine 6"));
-        }
-        // Known: 0, 1, 3, 4, 6, 7, 8, 9
-        {
-            // 5 is completely contained in 6 but not in 2
-            let six = digits.get(&6).unwrap();
-            let five = len_5.iter()
-                .enumerate()
-                .filter(|&(_index, digit)| Self::contains_all(six, digit))
-                .nth(0)
-                .expect("Could not determine 5");
-
-            digits.insert(5, len_5.remove(five.0));
-            digits.insert(2, len_5.pop().expect("Could not determine 2"));
-        }
-
-        let output_digits = input_output.next().expect("Could not determine output digits");
-        let mut output = Vec::new();
-
-        for output_digit in output_digits.split(' ') {
-            // println!("output_digit={0:?}", output_digit);
-
-            let output_digit = digits.iter()
-                .filter(|&(_digit, segments)| *segments == BTreeSet::from_iter(output_digit.chars()))
-                .nth(0);
-
-            match output_digit {
-                Some(v) => output.push(*v.0),
-                None => (),
+        candidates
+    }
+
+    fn search(&self, wire: usize, used: Segments, candidates: &[Segments], wiring: &mut Vec<usize>, wirings: &mut Vec<Vec<usize>>) {
+        if wirings.len() > MAX_WIRINGS {
+            return;
+        }
+
+        if wire == self.alphabet.segment_count {
+            wirings.push(wiring.clone());
+            return;
+        }
+
+        let mut remaining = candidates[wire] & !used;
+
+        while remaining != 0 {
+            let segment = remaining.trailing_zeros() as usize;
+            remaining &= remaining - 1;
+
+            wiring[wire] = segment;
+
+            if self.is_consistent(wire + 1, wiring) {
+                self.search(wire + 1, used | (1 << segment), candidates, wiring, wirings);
             }
         }
 
+        wiring[wire] = usize::MAX;
+    }
+
+    // Whether every pattern can still become a glyph, given the segments of the first `assigned` wires
+    fn is_consistent(&self, assigned: usize, wiring: &[usize]) -> bool {
+        let assigned_wires = Segments::MAX.checked_shr(Segments::BITS - assigned as u32).unwrap_or(0);
+        let assigned_segments = wiring[..assigned].iter()
+            .fold(0, |segments, &segment| segments | (1 << segment));
+
+        self.patterns.iter().all(|&pattern| {
+            let lit = Self::map_segments(pattern & assigned_wires, wiring);
+
+            self.alphabet.glyphs.iter().any(|&glyph| {
+                glyph.count_ones() == pattern.count_ones() && glyph & assigned_segments == lit
+            })
+        })
+    }
+
+    fn map_segments(wires: Segments, wiring: &[usize]) -> Segments {
+        let mut segments = 0;
+        let mut remaining = wires;
+
+        while remaining != 0 {
+            let wire = remaining.trailing_zeros() as usize;
+            remaining &= remaining - 1;
+            segments |= 1 << wiring[wire];
+        }
+
+        segments
+    }
+}
+
+#[derive(Debug)]
+struct SegmentDisplay {
+    display_patterns: Vec<Segments>,
+    // None where the output pattern names segments the display does not have
+    output_patterns: Vec<Option<Segments>>,
+    invalid_patterns: Vec<String>,
+    // Truncated lines lack the `|`, everything on them is taken as display patterns
+    missing_separator: bool,
+    wiring: Wiring,
+    // Index into the alphabet for every output pattern
+    output: Vec<usize>,
+}
+
+impl SegmentDisplay {
+    fn from_line(line: &str, alphabet: &Alphabet) -> Self {
+        let (display_digits, output_digits) = line.split_once('|').unwrap_or((line, ""));
+
+        let invalid_patterns: Vec<String> = line.split(|c: char| c.is_whitespace() || c == '|')
+            .filter(|pattern| !pattern.is_empty() && alphabet.try_parse_segments(pattern).is_none())
+            .map(str::to_owned)
+            .collect();
+
+        // Misread patterns are left out, so that the rest of the line can still be solved
+        let display_patterns: Vec<Segments> = display_digits.split_whitespace()
+            .filter_map(|pattern| alphabet.try_parse_segments(pattern))
+            .collect();
+        let output_patterns: Vec<Option<Segments>> = output_digits.split_whitespace()
+            .map(|pattern| alphabet.try_parse_segments(pattern))
+            .collect();
+
+        // The output digits are observations as well, they constrain the wiring just the same
+        let observed: Vec<Segments> = display_patterns.iter().chain(output_patterns.iter().flatten()).copied().collect();
+        let wiring = WiringSolver::new(alphabet, &observed).solve();
+        // println!("wiring={0:?}", wiring);
+
+        let output = match &wiring {
+            Wiring::Unique(wiring) => output_patterns.iter()
+                .map(|&pattern| pattern.map(|pattern| {
+                    alphabet.symbol_of(WiringSolver::map_segments(pattern, wiring))
+                        .expect("Solved wiring does not decode the output")
+                }))
+                .collect::<Option<Vec<usize>>>()
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        };
+
         Self {
-            digits,
+            display_patterns,
+            output_patterns,
+            invalid_patterns,
+            missing_separator: !line.contains('|'),
+            wiring,
             output,
         }
     }
 
-    #[inline]
-    fn contains_all(superset: &BTreeSet<char>, subset: &BTreeSet<char>) -> bool {
-        subset.iter().all(|element| superset.contains(element))
+    fn observed_patterns(&self) -> Vec<Segments> {
+        self.display_patterns.iter().chain(self.output_patterns.iter().flatten()).copied().collect()
+    }
+
+    // Whether every output pattern has been decoded to a symbol
+    fn is_decoded(&self) -> bool {
+        !self.output_patterns.is_empty() && self.output.len() == self.output_patterns.len()
+    }
+
+    // The symbol a pattern shows if all candidate wirings agree on it
+    fn determined_symbol(pattern: Segments, wirings: &[Vec<usize>], alphabet: &Alphabet) -> Option<usize> {
+        let mut symbols = wirings.iter()
+            .map(|wiring| alphabet.symbol_of(WiringSolver::map_segments(pattern, wiring)));
+        let first = symbols.next()??;
+
+        symbols.all(|symbol| symbol == Some(first)).then_some(first)
+    }
+
+    fn format_candidate_count(wirings: &[Vec<usize>]) -> String {
+        if wirings.len() > MAX_WIRINGS {
+            format!("{0}+", MAX_WIRINGS)
+        } else {
+            wirings.len().to_string()
+        }
     }
 
-    fn output_value(&self) -> i32 {
+    fn print_diagnosis(&self, line_number: usize, alphabet: &Alphabet) {
+        let wirings = match &self.wiring {
+            Wiring::Unique(wiring) => vec![wiring.clone()],
+            Wiring::Ambiguous(wirings) => wirings.clone(),
+            Wiring::Inconsistent => Vec::new(),
+        };
+
+        let mut unique_patterns = self.display_patterns.clone();
+        unique_patterns.sort_unstable();
+        unique_patterns.dedup();
+
+        println!("line={0:?} patterns={1:?} unique={2:?} duplicates={3:?} candidates={4}",
+                 line_number, self.display_patterns.len(), unique_patterns.len(),
+                 self.display_patterns.len() - unique_patterns.len(), Self::format_candidate_count(&wirings));
+
+        if !self.invalid_patterns.is_empty() {
+            println!("  invalid=[{0}]", self.invalid_patterns.join(" "));
+        }
+
+        if self.missing_separator {
+            println!("  separator=missing outputs=0");
+        }
+
+        if wirings.is_empty() {
+            // Patterns without which the rest of the line does have a wiring, most likely misread ones
+            let mut observed = self.observed_patterns();
+            observed.sort_unstable();
+            observed.dedup();
+
+            let suspects = observed.iter()
+                .filter(|&&suspect| {
+                    let others: Vec<Segments> = observed.iter().copied().filter(|&pattern| pattern != suspect).collect();
+                    WiringSolver::new(alphabet, &others).solve() != Wiring::Inconsistent
+                })
+                .map(|&suspect| Alphabet::format_segments(suspect))
+                .collect::<Vec<String>>();
+            println!("  inconsistent suspects=[{0}]", suspects.join(" "));
+            return;
+        }
+
+        let mut observed = self.observed_patterns();
+        observed.sort_unstable();
+        observed.dedup();
+
+        let mut determined = observed.iter()
+            .filter_map(|&pattern| Self::determined_symbol(pattern, &wirings, alphabet).map(|symbol| (symbol, pattern)))
+            .collect::<Vec<(usize, Segments)>>();
+        determined.sort_unstable();
+
+        let determined = determined.iter()
+            .map(|&(symbol, pattern)| format!("{0}={1}", alphabet.symbols[symbol], Alphabet::format_segments(pattern)))
+            .collect::<Vec<String>>();
+        println!("  determined=[{0}]", determined.join(" "));
+
+        const MAX_LISTED: usize = 10;
+        for wiring in wirings.iter().take(MAX_LISTED) {
+            // The segment driven by each wire, in wire order
+            let segments = wiring.iter()
+                .map(|&segment| (b'a' + segment as u8) as char)
+                .collect::<String>();
+            println!("  wiring={0}", segments);
+        }
+        if wirings.len() > MAX_LISTED {
+            println!("  ...");
+        }
+
+        let output = self.output_patterns.iter()
+            .map(|&pattern| pattern.and_then(|pattern| Self::determined_symbol(pattern, &wirings, alphabet)))
+            .collect::<Vec<Option<usize>>>();
+        let undecodable = output.iter()
+            .enumerate()
+            .filter(|(_index, symbol)| symbol.is_none())
+            .map(|(index, _symbol)| index.to_string())
+            .collect::<Vec<String>>();
+        let output = output.iter()
+            .map(|symbol| symbol.map_or("?", |symbol| alphabet.symbols[symbol].as_str()))
+            .collect::<String>();
+        println!("  output={0:?} undecodable=[{1}]", output, undecodable.join(" "));
+    }
+
+    fn output_text(&self, alphabet: &Alphabet) -> String {
         self.output.iter()
-            .map(|&d| std::char::from_digit(d as u32, 10).unwrap())
-            .collect::<String>()
+            .map(|&symbol| alphabet.symbols[symbol].as_str())
+            .collect()
+    }
+
+    fn output_value(&self, alphabet: &Alphabet) -> i32 {
+        self.output_text(alphabet)
             .parse::<i32>()
             .expect("Could not parse output value")
     }
 }
 
+#[derive(Debug, Default)]
+struct Options {
+    alphabet_path: Option<String>,
+    diagnose: bool,
+}
+
+impl Options {
+    fn from_args() -> Self {
+        let mut options = Self::default();
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--alphabet" => options.alphabet_path = Some(args.next().expect("Missing path for --alphabet")),
+                "--diagnose" => options.diagnose = true,
+                _ => panic!("Invalid argument: {0:?}", arg),
+            }
+        }
+
+        options
+    }
+}
+
 fn main() {
+    let options = Options::from_args();
+
+    let alphabet = match &options.alphabet_path {
+        Some(path) => Alphabet::from_file(path),
+        None => Alphabet::digits(),
+    };
+
     let lines: Vec<String> = stdin().lock().lines()
         .map(|line| line.expect("Cannot read line"))
         .collect();
@@ -138,23 +443,42 @@ fn main() {
     let mut parsed_lines = Vec::new();
     parsed_lines.reserve_exact(lines.len());
 
-    for line in lines {
-        let display = SevenSegmentDisplay::from_line(&line);
+    for (index, line) in lines.iter().enumerate() {
+        let display = SegmentDisplay::from_line(line, &alphabet);
         // println!("display={0:?}", display);
 
-        parsed_lines.push(display);
+        if options.diagnose {
+            display.print_diagnosis(index + 1, &alphabet);
+        }
+
+        match &display.wiring {
+            Wiring::Unique(_wiring) if !display.is_decoded() => println!("line={0:?} output=undecodable", index + 1),
+            // Other alphabets have no puzzle answers, so just show what each line reads
+            Wiring::Unique(_wiring) if options.alphabet_path.is_some() => {
+                println!("line={0:?} output={1:?}", index + 1, display.output_text(&alphabet));
+            }
+            Wiring::Unique(_wiring) => parsed_lines.push(display),
+            Wiring::Ambiguous(wirings) => {
+                println!("line={0:?} wiring=ambiguous candidates={1}", index + 1, SegmentDisplay::format_candidate_count(wirings));
+            }
+            Wiring::Inconsistent => println!("line={0:?} wiring=inconsistent", index + 1),
+        }
+    }
+
+    if options.alphabet_path.is_some() {
+        return;
     }
 
-    let unique_numbers = vec![1, 4, 7, 8];
+    let unique_numbers = ["1", "4", "7", "8"];
     let unique_numbers_count = parsed_lines.iter()
         .flat_map(|parsed_line| parsed_line.output.iter())
-        .filter(|&output_digit| unique_numbers.contains(output_digit))
+        .filter(|&&output_digit| unique_numbers.contains(&alphabet.symbols[output_digit].as_str()))
         .count();
     println!("part1: unique_numbers={0:?}", unique_numbers_count);
-    assert_eq!(unique_numbers, 392);
+    assert_eq!(unique_numbers_count, 392);
 
     let output_sum: i32 = parsed_lines.iter()
-        .map(|parsed_line| parsed_line.output_value())
+        .map(|parsed_line| parsed_line.output_value(&alphabet))
         .sum();
     println!("part2: output_sum={0:?}", output_sum);
     assert_eq!(output_sum, 1004688);
@@ -1,10 +1,81 @@
-use std::collections::{HashSet, VecDeque};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs;
 use std::io::{BufRead, stdin};
-use std::ops::Range;
+use std::ops::{Range, RangeInclusive};
 
 type CoordType = i32;
 type ItemType = u32;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Connectivity {
+    Four,
+    Eight,
+}
+
+impl Connectivity {
+    fn offsets(self) -> &'static [(CoordType, CoordType)] {
+        match self {
+            Connectivity::Four => &[(0, -1), (-1, 0), (1, 0), (0, 1)],
+            Connectivity::Eight => &[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)],
+        }
+    }
+
+    // The neighbours that come before a cell in reading order
+    fn preceding_offsets(self) -> &'static [(CoordType, CoordType)] {
+        match self {
+            Connectivity::Four => &[(0, -1), (-1, 0)],
+            Connectivity::Eight => &[(-1, -1), (0, -1), (1, -1), (-1, 0)],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Rules {
+    connectivity: Connectivity,
+    // Heights that belong to no basin
+    barrier: RangeInclusive<ItemType>,
+    // Whether a connected area of equal height without lower neighbours counts as a single low point
+    plateaus: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            connectivity: Connectivity::Four,
+            barrier: 9..=ItemType::MAX,
+            plateaus: false,
+        }
+    }
+}
+
+impl Rules {
+    // Format: `<height>` for that height and above, or `<lowest>-<highest>`
+    fn parse_barrier(barrier: &str) -> RangeInclusive<ItemType> {
+        let parse = |height: &str| height.parse::<ItemType>().unwrap_or_else(|_| panic!("Invalid barrier: {0:?}", barrier));
+
+        let range = match barrier.split_once('-') {
+            Some((lowest, highest)) => parse(lowest)..=parse(highest),
+            None => parse(barrier)..=ItemType::MAX,
+        };
+
+        assert!(range.start() <= range.end(), "Invalid barrier, lowest height exceeds highest: {0:?}", barrier);
+        range
+    }
+
+    fn format_barrier(&self) -> String {
+        match (self.barrier.start(), self.barrier.end()) {
+            (lowest, &ItemType::MAX) => format!("{0}-", lowest),
+            (lowest, highest) => format!("{0}-{1}", lowest, highest),
+        }
+    }
+
+    #[inline]
+    fn is_basin(&self, value: &ItemType) -> bool {
+        !self.barrier.contains(value)
+    }
+}
+
 #[derive(Debug)]
 struct Grid {
     width: CoordType,
@@ -43,25 +114,30 @@ impl Grid {
         self.heightmap.get((y * self.width + x) as usize)
     }
 
-    fn neighbors_match(&self, x: CoordType, y: CoordType, predicate: impl Fn(&ItemType, CoordType, CoordType) -> bool) -> bool {
-        let mid = self.at(x, y).expect("Could not determine mid");
+    fn neighbors(&self, x: CoordType, y: CoordType, offsets: &'static [(CoordType, CoordType)]) -> impl Iterator<Item = (CoordType, CoordType)> + '_ {
+        offsets.iter()
+            .map(move |&(dx, dy)| (x + dx, y + dy))
+            .filter(|&(x, y)| self.is_inside(x, y))
+    }
 
-        for (x, y) in [(x, y - 1), (x - 1, y), (x + 1, y), (x, y + 1)] {
-            if self.is_inside(x, y) && !predicate(mid, x, y) {
-                return false;
-            }
-        }
+    fn neighbors_match(&self, x: CoordType, y: CoordType, connectivity: Connectivity, predicate: impl Fn(&ItemType, CoordType, CoordType) -> bool) -> bool {
+        let mid = self.at(x, y).expect("Could not determine mid");
 
-        true
+        self.neighbors(x, y, connectivity.offsets())
+            .all(|(x, y)| predicate(mid, x, y))
     }
 
-    fn all_lowest_points(&self) -> Vec<(&ItemType, CoordType, CoordType)> {
+    fn all_lowest_points(&self, rules: &Rules) -> Vec<(&ItemType, CoordType, CoordType)> {
+        if rules.plateaus {
+            return self.all_lowest_plateaus(rules.connectivity);
+        }
+
         let mut lowest_points = Vec::new();
 
         for row in self.vertical.clone() {
             for column in self.horizontal.clone() {
-                if self.neighbors_match(column, row, |mid, x, y| {
-                    self.at(x, y).map_or(false, |value| mid < value)
+                if self.neighbors_match(column, row, rules.connectivity, |mid, x, y| {
+                    self.at(x, y).is_some_and(|value| mid < value)
                 }) {
                     let value = self.at(column, row)
                         .expect("Could not get lowest point");
@@ -73,41 +149,542 @@ impl Grid {
         lowest_points
     }
 
+    // Joins neighbouring cells of equal height, so that every set is a plateau (or a single cell)
+    fn join_plateaus(&self, connectivity: Connectivity) -> UnionFind {
+        let mut union_find = UnionFind::new(self.heightmap.len());
+
+        for row in self.vertical.clone() {
+            for column in self.horizontal.clone() {
+                let value = self.at(column, row).unwrap();
+
+                for (x, y) in self.neighbors(column, row, connectivity.preceding_offsets()) {
+                    if self.at(x, y) == Some(value) {
+                        union_find.union(self.index(column, row), self.index(x, y));
+                    }
+                }
+            }
+        }
+
+        union_find
+    }
+
+    // Every plateau without a lower neighbour is one low point, represented by its first cell in reading order
+    fn all_lowest_plateaus(&self, connectivity: Connectivity) -> Vec<(&ItemType, CoordType, CoordType)> {
+        let mut union_find = self.join_plateaus(connectivity);
+
+        let mut has_lower_neighbor = vec![false; self.heightmap.len()];
+        let mut first_cells = vec![None; self.heightmap.len()];
+
+        for row in self.vertical.clone() {
+            for column in self.horizontal.clone() {
+                let root = union_find.find(self.index(column, row));
+                first_cells[root].get_or_insert((column, row));
+
+                if !self.neighbors_match(column, row, connectivity, |mid, x, y| {
+                    self.at(x, y).is_some_and(|value| mid <= value)
+                }) {
+                    has_lower_neighbor[root] = true;
+                }
+            }
+        }
+
+        let mut lowest_points = Vec::new();
+
+        for row in self.vertical.clone() {
+            for column in self.horizontal.clone() {
+                let root = union_find.find(self.index(column, row));
+
+                if first_cells[root] == Some((column, row)) && !has_lower_neighbor[root] {
+                    lowest_points.push((self.at(column, row).unwrap(), column, row));
+                }
+            }
+        }
+
+        lowest_points
+    }
+
     fn is_inside(&self, x: CoordType, y: CoordType) -> bool {
         self.horizontal.contains(&x) && self.vertical.contains(&y)
     }
 
-    fn find_regions(&self, x: CoordType, y: CoordType, predicate: impl Fn(i32, i32) -> bool) -> Vec<&ItemType> {
-        let mut values = Vec::new();
+    #[inline]
+    fn index(&self, x: CoordType, y: CoordType) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    // Union-find over all cells in a single pass: every basin cell is joined with its basin neighbours that come
+    // earlier in reading order, which links up each basin as a whole
+    fn segment_basins(&self, rules: &Rules) -> Segmentation {
+        let mut union_find = UnionFind::new(self.heightmap.len());
+
+        for row in self.vertical.clone() {
+            for column in self.horizontal.clone() {
+                if !rules.is_basin(self.at(column, row).unwrap()) {
+                    continue;
+                }
+
+                for (x, y) in self.neighbors(column, row, rules.connectivity.preceding_offsets()) {
+                    if rules.is_basin(self.at(x, y).unwrap()) {
+                        union_find.union(self.index(column, row), self.index(x, y));
+                    }
+                }
+            }
+        }
+
+        let mut labels = vec![None; self.heightmap.len()];
+        let mut label_by_root = vec![None; self.heightmap.len()];
+        let mut basins: Vec<Basin> = Vec::new();
+        let mut barriers = Vec::new();
+
+        // Basins are numbered in the order their first cell appears, row by row
+        for row in self.vertical.clone() {
+            for column in self.horizontal.clone() {
+                let index = self.index(column, row);
+                let value = *self.at(column, row).unwrap();
+
+                if !rules.is_basin(&value) {
+                    barriers.push((column, row));
+                    continue;
+                }
+
+                let root = union_find.find(index);
+                let id = *label_by_root[root].get_or_insert_with(|| {
+                    basins.push(Basin::new(basins.len(), value, column, row));
+                    basins.len() - 1
+                });
+
+                labels[index] = Some(id);
+                basins[id].add(value, column, row);
+            }
+        }
+
+        // Every side of a cell that borders another basin, a barrier or the edge of the map counts towards the perimeter
+        for row in self.vertical.clone() {
+            for column in self.horizontal.clone() {
+                if let Some(id) = labels[self.index(column, row)] {
+                    basins[id].perimeter += [(column, row - 1), (column - 1, row), (column + 1, row), (column, row + 1)].iter()
+                        .filter(|&&(x, y)| !self.is_inside(x, y) || labels[self.index(x, y)] != Some(id))
+                        .count();
+                }
+            }
+        }
+
+        Segmentation {
+            width: self.width,
+            height: self.height,
+            labels,
+            basins,
+            barriers,
+        }
+    }
+
+    // Steepest descent from every cell. Cells on a plateau flow towards its nearest edge with a way down, while
+    // plateaus without one (and single cells without lower neighbours) are sinks.
+    fn flow_map(&self, connectivity: Connectivity) -> FlowMap {
+        let offsets = connectivity.offsets();
+        let len = self.heightmap.len();
+
+        let mut drainage = vec![Drainage::Sink; len];
+        // Steps to the nearest cell of the same plateau that has a lower neighbour
+        let mut distances = vec![usize::MAX; len];
+        let mut queue = VecDeque::new();
+
+        for row in self.vertical.clone() {
+            for column in self.horizontal.clone() {
+                let value = *self.at(column, row).unwrap();
+                let drops: Vec<(usize, ItemType)> = offsets.iter()
+                    .enumerate()
+                    .map(|(direction, &(dx, dy))| (direction, (column + dx, row + dy)))
+                    .filter(|&(_direction, (x, y))| self.is_inside(x, y))
+                    .map(|(direction, (x, y))| (direction, value.saturating_sub(*self.at(x, y).unwrap())))
+                    .collect();
+
+                let steepest = drops.iter().map(|&(_direction, drop)| drop).max().unwrap_or(0);
+
+                if steepest > 0 {
+                    let index = self.index(column, row);
+                    drainage[index] = Drainage::Flows(drops.iter()
+                        .filter(|&&(_direction, drop)| drop == steepest)
+                        .map(|&(direction, _drop)| direction)
+                        .collect());
+                    distances[index] = 0;
+                    queue.push_back((column, row));
+                }
+            }
+        }
+
+        // Breadth-first across plateaus, starting from the cells where water leaves them
+        while let Some((column, row)) = queue.pop_front() {
+            let distance = distances[self.index(column, row)];
+            let value = self.at(column, row);
+
+            for (x, y) in self.neighbors(column, row, offsets) {
+                let index = self.index(x, y);
+
+                if self.at(x, y) == value && distances[index] == usize::MAX {
+                    distances[index] = distance + 1;
+                    queue.push_back((x, y));
+                }
+            }
+        }
+
+        for row in self.vertical.clone() {
+            for column in self.horizontal.clone() {
+                let distance = distances[self.index(column, row)];
+
+                if distance != 0 && distance != usize::MAX {
+                    drainage[self.index(column, row)] = Drainage::Flows(offsets.iter()
+                        .enumerate()
+                        .filter(|&(_direction, &(dx, dy))| {
+                            self.is_inside(column + dx, row + dy) && distances[self.index(column + dx, row + dy)] == distance - 1
+                                && self.at(column + dx, row + dy) == self.at(column, row)
+                        })
+                        .map(|(direction, _offset)| direction)
+                        .collect());
+                }
+            }
+        }
+
+        // A sink plateau is one sink, numbered by its first cell in reading order
+        let mut union_find = self.join_plateaus(connectivity);
+        let mut sink_by_root = vec![None; len];
+        let mut sinks = Vec::new();
+        let mut targets = vec![None; len];
+        let mut plateaus = vec![0; len];
+
+        for row in self.vertical.clone() {
+            for column in self.horizontal.clone() {
+                let index = self.index(column, row);
+                let root = union_find.find(index);
+                plateaus[root] += 1;
+
+                if drainage[index] == Drainage::Sink {
+                    let sink = *sink_by_root[root].get_or_insert_with(|| {
+                        sinks.push((column, row));
+                        sinks.len() - 1
+                    });
+                    targets[index] = Some(sink);
+                }
+            }
+        }
+
+        // Water only flows downhill or closer to the edge of a plateau, so in this order every cell comes after
+        // the cells it flows into
+        let mut order: Vec<usize> = (0..len).collect();
+        order.sort_by_key(|&index| (self.heightmap[index], distances[index]));
+
+        let mut ambiguous = vec![false; len];
+
+        for index in order {
+            let directions = match &drainage[index] {
+                Drainage::Flows(directions) => directions,
+                Drainage::Sink => continue,
+            };
+            let (column, row) = (index as CoordType % self.width, index as CoordType / self.width);
+
+            let mut downstream = directions.iter().map(|&direction| {
+                let (dx, dy) = offsets[direction];
+                self.index(column + dx, row + dy)
+            });
+            let first = downstream.next().unwrap();
+
+            if ambiguous[first] || downstream.any(|next| ambiguous[next] || targets[next] != targets[first]) {
+                ambiguous[index] = true;
+            } else {
+                targets[index] = targets[first];
+            }
+        }
+
+        FlowMap {
+            width: self.width,
+            connectivity,
+            drainage,
+            targets,
+            sinks,
+            plateau_sizes: plateaus.into_iter().filter(|&size| size > 1).collect(),
+        }
+    }
+}
+
+// How water leaves a cell
+#[derive(Clone, Debug, PartialEq)]
+enum Drainage {
+    // Every equally steep way down, as indices into the neighbour offsets
+    Flows(Vec<usize>),
+    Sink,
+}
+
+#[derive(Debug)]
+struct FlowMap {
+    width: CoordType,
+    connectivity: Connectivity,
+    drainage: Vec<Drainage>,
+    // The sink every cell drains into, None where its ways down end in different sinks
+    targets: Vec<Option<usize>>,
+    // First cell of every sink in reading order
+    sinks: Vec<(CoordType, CoordType)>,
+    plateau_sizes: Vec<usize>,
+}
+
+impl FlowMap {
+    fn ambiguous_count(&self) -> usize {
+        self.targets.iter()
+            .filter(|target| target.is_none())
+            .count()
+    }
+
+    // Cells that have more than one steepest way down, whether or not these end in the same sink
+    fn tied_count(&self) -> usize {
+        self.drainage.iter()
+            .filter(|drainage| matches!(drainage, Drainage::Flows(directions) if directions.len() > 1))
+            .count()
+    }
+
+    fn catchment_areas(&self) -> Vec<usize> {
+        let mut areas = vec![0; self.sinks.len()];
+
+        for &sink in self.targets.iter().flatten() {
+            areas[sink] += 1;
+        }
+
+        areas
+    }
+
+    // An arrow along the (first) steepest descent, `o` for sinks and `?` where the drainage is ambiguous
+    fn to_arrow_map(&self) -> String {
+        let offsets = self.connectivity.offsets();
+        let mut map = String::new();
+
+        for (index, drainage) in self.drainage.iter().enumerate() {
+            map.push(match (drainage, self.targets[index]) {
+                (Drainage::Sink, _target) => 'o',
+                (Drainage::Flows(_directions), None) => '?',
+                (Drainage::Flows(directions), Some(_target)) => match offsets[directions[0]] {
+                    (-1, -1) => '↖',
+                    (0, -1) => '↑',
+                    (1, -1) => '↗',
+                    (-1, 0) => '←',
+                    (1, 0) => '→',
+                    (-1, 1) => '↙',
+                    (0, 1) => '↓',
+                    (1, 1) => '↘',
+                    offset => panic!("Invalid flow direction: {0:?}", offset),
+                },
+            });
+
+            if (index + 1) % self.width as usize == 0 {
+                map.push('\n');
+            }
+        }
+
+        map
+    }
+
+    // The area drained by every sink, with the basin the sink lies in if any
+    fn catchments_csv(&self, grid: &Grid, segmentation: &Segmentation) -> String {
+        let mut csv = String::from("sink,x,y,height,basin,area\n");
+
+        for (sink, (&(x, y), area)) in self.sinks.iter().zip(self.catchment_areas()).enumerate() {
+            let basin = segmentation.labels[grid.index(x, y)]
+                .map_or(String::new(), |basin| basin.to_string());
+            writeln!(csv, "{0},{1},{2},{3},{4},{5}", sink, x, y, grid.at(x, y).unwrap(), basin, area).unwrap();
+        }
+
+        csv
+    }
+}
+
+#[derive(Debug)]
+struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
 
-        let mut visited: HashSet<(CoordType, CoordType)> = HashSet::new();
-        visited.insert((x, y));
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+            size: vec![1; len],
+        }
+    }
 
-        let mut seed_points = VecDeque::new();
-        seed_points.push_front((x, y));
+    fn find(&mut self, mut element: usize) -> usize {
+        // Path halving: every visited element skips to its grandparent
+        while self.parent[element] != element {
+            self.parent[element] = self.parent[self.parent[element]];
+            element = self.parent[element];
+        }
 
-        while !seed_points.is_empty() {
-            let p = seed_points.pop_front()
-                .expect("Could not get next seed point");
+        element
+    }
 
-            let value = self.at(p.0, p.1)
-                .expect("Could not get mid point");
+    fn union(&mut self, lhs: usize, rhs: usize) {
+        let (lhs, rhs) = (self.find(lhs), self.find(rhs));
 
-            values.push(value);
+        if lhs == rhs {
+            return;
+        }
+
+        let (larger, smaller) = if self.size[lhs] >= self.size[rhs] { (lhs, rhs) } else { (rhs, lhs) };
+        self.parent[smaller] = larger;
+        self.size[larger] += self.size[smaller];
+    }
+}
+
+#[derive(Debug)]
+struct Basin {
+    id: usize,
+    size: usize,
+    // The lowest cell, the first one in reading order on ties
+    low_point: (ItemType, CoordType, CoordType),
+    // Inclusive corners (min_x, min_y) and (max_x, max_y)
+    bounding_box: (CoordType, CoordType, CoordType, CoordType),
+    perimeter: usize,
+}
 
-            for (x, y) in [(p.0, p.1 - 1 as CoordType), (p.0 - 1, p.1), (p.0 + 1, p.1), (p.0, p.1 + 1)] {
-                if !visited.contains(&(x, y)) && self.is_inside(x, y) && predicate(x, y) {
-                    seed_points.push_back((x, y));
-                    visited.insert((x, y));
+impl Basin {
+    fn new(id: usize, value: ItemType, x: CoordType, y: CoordType) -> Self {
+        Self {
+            id,
+            size: 0,
+            low_point: (value, x, y),
+            bounding_box: (x, y, x, y),
+            perimeter: 0,
+        }
+    }
+
+    fn add(&mut self, value: ItemType, x: CoordType, y: CoordType) {
+        self.size += 1;
+
+        if value < self.low_point.0 {
+            self.low_point = (value, x, y);
+        }
+
+        let (min_x, min_y, max_x, max_y) = self.bounding_box;
+        self.bounding_box = (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y));
+    }
+}
+
+#[derive(Debug)]
+struct Segmentation {
+    width: CoordType,
+    height: CoordType,
+    // Basin id of every cell, None for barrier cells
+    labels: Vec<Option<usize>>,
+    basins: Vec<Basin>,
+    barriers: Vec<(CoordType, CoordType)>,
+}
+
+impl Segmentation {
+    fn product_three_largest_basins(&self) -> usize {
+        let mut basin_sizes: Vec<usize> = self.basins.iter()
+            .map(|basin| basin.size)
+            .collect();
+        basin_sizes.sort_unstable_by(|a, b| b.cmp(a));
+        // println!("basin_sizes={0:?}", basin_sizes);
+
+        basin_sizes.iter()
+            .take(3)
+            .product()
+    }
+
+    fn to_json(&self) -> String {
+        let mut json = String::from("{\n  \"basins\": [");
+
+        for (index, basin) in self.basins.iter().enumerate() {
+            let (value, x, y) = basin.low_point;
+            let (min_x, min_y, max_x, max_y) = basin.bounding_box;
+
+            json += if index == 0 { "\n" } else { ",\n" };
+            json += &format!(
+                "    {{\"id\": {0}, \"size\": {1}, \"low_point\": {{\"x\": {2}, \"y\": {3}, \"height\": {4}}}, \
+                 \"bounding_box\": {{\"min_x\": {5}, \"min_y\": {6}, \"max_x\": {7}, \"max_y\": {8}}}, \"perimeter\": {9}}}",
+                basin.id, basin.size, x, y, value, min_x, min_y, max_x, max_y, basin.perimeter);
+        }
+
+        let barriers = self.barriers.iter()
+            .map(|(x, y)| format!("[{0}, {1}]", x, y))
+            .collect::<Vec<String>>()
+            .join(", ");
+        json += &format!("\n  ],\n  \"barriers\": [{0}]\n}}\n", barriers);
+
+        json
+    }
+
+    // One basin id per cell, padded to the widest id, with barrier cells shown as `#`
+    fn to_label_grid(&self) -> String {
+        let cell_width = self.basins.len().saturating_sub(1).to_string().len();
+        let mut grid = String::new();
+
+        for row in self.labels.chunks(self.width as usize).take(self.height as usize) {
+            let cells = row.iter()
+                .map(|label| match label {
+                    Some(id) => format!("{0:>1$}", id, cell_width),
+                    None => format!("{0:>1$}", "#", cell_width),
+                })
+                .collect::<Vec<String>>()
+                .join(" ");
+
+            grid += &cells;
+            grid += "\n";
+        }
+
+        grid
+    }
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    json_path: Option<String>,
+    labels_path: Option<String>,
+    rules: Rules,
+    arrows_path: Option<String>,
+    catchments_path: Option<String>,
+}
+
+impl Options {
+    fn from_args() -> Self {
+        let mut options = Self::default();
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--json" => options.json_path = Some(args.next().expect("Missing path for --json")),
+                "--labels" => options.labels_path = Some(args.next().expect("Missing path for --labels")),
+                "--arrows" => options.arrows_path = Some(args.next().expect("Missing path for --arrows")),
+                "--catchments" => options.catchments_path = Some(args.next().expect("Missing path for --catchments")),
+                "--connectivity" => {
+                    options.rules.connectivity = match args.next().expect("Missing neighbour count for --connectivity").as_str() {
+                        "4" => Connectivity::Four,
+                        "8" => Connectivity::Eight,
+                        connectivity => panic!("Invalid connectivity: {0:?}", connectivity),
+                    }
+                }
+                "--barrier" => options.rules.barrier = Rules::parse_barrier(&args.next().expect("Missing height for --barrier")),
+                "--low-points" => {
+                    options.rules.plateaus = match args.next().expect("Missing kind for --low-points").as_str() {
+                        "strict" => false,
+                        "plateau" => true,
+                        low_points => panic!("Invalid low points: {0:?}", low_points),
+                    }
                 }
+                _ => panic!("Invalid argument: {0:?}", arg),
             }
         }
 
-        values
+        options
     }
 }
 
+fn total_risk_level(lowest_points: &[(&ItemType, CoordType, CoordType)]) -> ItemType {
+    lowest_points.iter()
+        .map(|low_point| low_point.0 + 1)
+        .sum()
+}
+
 fn main() {
+    let options = Options::from_args();
+
     let lines: Vec<String> = stdin().lock().lines()
         .map(|line| line.expect("Cannot read line"))
         .collect();
@@ -115,29 +692,72 @@ fn main() {
     let grid = Grid::from_lines(&lines);
     // println!("grid={0:?}", grid);
 
-    let lowest_points = grid.all_lowest_points();
-    let risk_level: ItemType = lowest_points.iter()
-        .map(|low_point| low_point.0 + 1)
-        .sum();
-    println!("part1: risk_level={0:?}", risk_level);
-    assert_eq!(risk_level, 500);
+    let default_rules = Rules::default();
 
-    let mut basins = Vec::new();
+    // Other heightmaps and the extra modes are not the puzzle, so there are no answers to check them against
+    let is_puzzle = options.rules == default_rules && options.json_path.is_none() && options.labels_path.is_none()
+        && options.arrows_path.is_none() && options.catchments_path.is_none();
 
-    for lowest_point in lowest_points.iter() {
-        basins.push(grid.find_regions(lowest_point.1, lowest_point.2, |x, y| {
-            grid.at(x, y).map_or(false, |value| value < &9)
-        }));
+    let lowest_points = grid.all_lowest_points(&default_rules);
+    let risk_level = total_risk_level(&lowest_points);
+    println!("part1: risk_level={0:?}", risk_level);
+
+    if is_puzzle {
+        assert_eq!(risk_level, 500);
     }
 
-    basins.sort_by(|a, b| (b.len().partial_cmp(&a.len())).unwrap());
-    // println!("basins={0:?}", basins);
+    let segmentation = grid.segment_basins(&default_rules);
+    println!("basins: count={0:?} barriers={1:?}", segmentation.basins.len(), segmentation.barriers.len());
 
-    let product_three_largest_basins = basins.iter()
-        .take(3)
-        .map(|basin| basin.len())
-        .map(|len| len)
-        .fold(1, |acc, value| acc * value);
+    let product_three_largest_basins = segmentation.product_three_largest_basins();
     println!("part2: product_three_largest_basins={0:?}", product_three_largest_basins);
-    assert_eq!(product_three_largest_basins, 970200);
+
+    if is_puzzle {
+        assert_eq!(product_three_largest_basins, 970200);
+    }
+
+    let segmentation = if options.rules != default_rules {
+        let lowest_points = grid.all_lowest_points(&options.rules);
+        let segmentation = grid.segment_basins(&options.rules);
+
+        println!("rules: connectivity={0:?} barrier={1:?} plateaus={2:?} low_points={3:?} risk_level={4:?} basins={5:?} barriers={6:?} product_three_largest_basins={7:?}",
+                 options.rules.connectivity, options.rules.format_barrier(), options.rules.plateaus, lowest_points.len(),
+                 total_risk_level(&lowest_points), segmentation.basins.len(), segmentation.barriers.len(),
+                 segmentation.product_three_largest_basins());
+
+        segmentation
+    } else {
+        segmentation
+    };
+
+    if let Some(path) = &options.json_path {
+        fs::write(path, segmentation.to_json())
+            .unwrap_or_else(|error| panic!("Cannot write basins to {0:?}: {1}", path, error));
+        println!("json={0:?}", path);
+    }
+
+    if let Some(path) = &options.labels_path {
+        fs::write(path, segmentation.to_label_grid())
+            .unwrap_or_else(|error| panic!("Cannot write basin labels to {0:?}: {1}", path, error));
+        println!("labels={0:?}", path);
+    }
+
+    if options.arrows_path.is_some() || options.catchments_path.is_some() {
+        let flow_map = grid.flow_map(options.rules.connectivity);
+        println!("flow: sinks={0:?} plateaus={1:?} plateau_cells={2:?} tied={3:?} ambiguous={4:?}",
+                 flow_map.sinks.len(), flow_map.plateau_sizes.len(), flow_map.plateau_sizes.iter().sum::<usize>(),
+                 flow_map.tied_count(), flow_map.ambiguous_count());
+
+        if let Some(path) = &options.arrows_path {
+            fs::write(path, flow_map.to_arrow_map())
+                .unwrap_or_else(|error| panic!("Cannot write arrow map to {0:?}: {1}", path, error));
+            println!("arrows={0:?}", path);
+        }
+
+        if let Some(path) = &options.catchments_path {
+            fs::write(path, flow_map.catchments_csv(&grid, &segmentation))
+                .unwrap_or_else(|error| panic!("Cannot write catchments to {0:?}: {1}", path, error));
+            println!("catchments={0:?}", path);
+        }
+    }
 }
@@ -1,98 +1,252 @@
-use std::collections::{HashMap, VecDeque};
+use std::fs;
 use std::io::{BufRead, stdin};
 
-fn main() {
-    let lines: Vec<String> = stdin().lock().lines()
-        .map(|line| line.expect("Cannot read line"))
-        .collect();
+#[derive(Clone, Debug)]
+struct Delimiter {
+    opening: String,
+    closing: String,
+    corruption_score: u64,
+    completion_score: u64,
+}
+
+impl Delimiter {
+    fn new(opening: &str, closing: &str, corruption_score: u64, completion_score: u64) -> Self {
+        assert!(!opening.is_empty() && !closing.is_empty(), "Delimiters cannot be empty");
+        // Otherwise the longer one always wins in `Grammar::token_at`, and the shorter one could never be read
+        assert!(!opening.starts_with(closing) && !closing.starts_with(opening),
+                "Opening and closing delimiters must not overlap: {0:?} {1:?}", opening, closing);
+
+        Self {
+            opening: opening.to_owned(),
+            closing: closing.to_owned(),
+            corruption_score,
+            completion_score,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token {
+    Opening(usize),
+    Closing(usize),
+}
+
+#[derive(Debug, PartialEq)]
+enum Check {
+    Complete,
+    // The pairs still open, innermost first
+    Incomplete(Vec<usize>),
+    // The closing delimiter that does not match, and the pair it should have closed (None if nothing was open)
+    Corrupted { found: usize, expected: Option<usize> },
+}
+
+#[derive(Debug)]
+struct Grammar {
+    pairs: Vec<Delimiter>,
+    completion_multiplier: u64,
+}
+
+impl Grammar {
+    fn brackets() -> Self {
+        Self {
+            pairs: vec![
+                Delimiter::new("(", ")", 3, 1),
+                Delimiter::new("[", "]", 57, 2),
+                Delimiter::new("{", "}", 1197, 3),
+                Delimiter::new("<", ">", 25137, 4),
+            ],
+            completion_multiplier: 5,
+        }
+    }
+
+    // Format: one `<opening> <closing> <corruption score> <completion score>` per line, e.g. `begin end 3 1`,
+    // and optionally `multiplier <n>` for the completion score; delimiters cannot contain whitespace
+    fn from_file(path: &str) -> Self {
+        let config = fs::read_to_string(path)
+            .unwrap_or_else(|error| panic!("Cannot read grammar from {0:?}: {1}", path, error));
+
+        let parse = |score: &str| score.parse::<u64>().unwrap_or_else(|_| panic!("Invalid score: {0:?}", score));
+
+        let mut grammar = Self {
+            pairs: Vec::new(),
+            completion_multiplier: 5,
+        };
 
-    let mut valid_pairs = HashMap::new();
+        for line in config.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            match line.split_whitespace().collect::<Vec<&str>>().as_slice() {
+                ["multiplier", multiplier] => grammar.completion_multiplier = parse(multiplier),
+                [opening, closing, corruption_score, completion_score] => {
+                    grammar.pairs.push(Delimiter::new(opening, closing, parse(corruption_score), parse(completion_score)));
+                }
+                _ => panic!("Invalid delimiter definition: {0:?}", line),
+            }
+        }
 
-    valid_pairs.insert('(', ')');
-    valid_pairs.insert('[', ']');
-    valid_pairs.insert('{', '}');
-    valid_pairs.insert('<', '>');
+        assert!(!grammar.pairs.is_empty(), "Grammar defines no delimiters");
 
-    let mut illegal_char_count = HashMap::new();
-    let mut completed_scores = Vec::new();
+        let mut texts: Vec<&str> = grammar.pairs.iter()
+            .flat_map(|delimiter| [delimiter.opening.as_str(), delimiter.closing.as_str()])
+            .collect();
+        texts.sort_unstable();
 
-    'next_line: for line in lines.iter() {
-        // println!("scanning line={0:?}", line);
+        if let Some(text) = texts.windows(2).find(|texts| texts[0] == texts[1]) {
+            panic!("Delimiter defined more than once: {0:?}", text[0]);
+        }
 
-        let mut this_line = VecDeque::new();
-        this_line.reserve_exact(line.len());
+        grammar
+    }
 
-        for token in line.chars() {
-            // Opening characters go on the stack
-            if valid_pairs.contains_key(&token) {
-                // println!("found opening {0:?}", token);
-                this_line.push_front(token);
-            } else {
-                // Otherwise we assume its the closing character, so check if it matches the top of stack
-                let last_opening = this_line.pop_front()
-                    .expect("Could not determine last opening char");
+    // The longest delimiter starting at `position`; word-like delimiters such as `begin` only match whole words
+    fn token_at(&self, line: &str, position: usize) -> Option<(Token, usize)> {
+        let rest = &line[position..];
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+        self.pairs.iter()
+            .enumerate()
+            .flat_map(|(pair, delimiter)| [
+                (Token::Opening(pair), delimiter.opening.as_str()),
+                (Token::Closing(pair), delimiter.closing.as_str()),
+            ])
+            .filter(|(_token, text)| rest.starts_with(text))
+            .filter(|(_token, text)| {
+                let before = line[..position].chars().next_back();
+                let after = rest[text.len()..].chars().next();
+
+                let splits_word_before = text.starts_with(is_word) && before.is_some_and(is_word);
+                let splits_word_after = text.ends_with(is_word) && after.is_some_and(is_word);
+
+                !(splits_word_before || splits_word_after)
+            })
+            .max_by_key(|(_token, text)| text.len())
+            .map(|(token, text)| (token, text.len()))
+    }
 
-                let expected_closing = valid_pairs.get(&last_opening)
-                    .expect(format!("Could not determine expected closing char for last_opening?{0:?}", last_opening).as_str());
+    // Splits a line into delimiters, skipping any text in between
+    fn tokenize(&self, line: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut position = 0;
 
-                // Legal pairing
-                if &token == expected_closing {
-                    // println!("found closing {0:?} (last_opening={1:?})", token, last_opening);
-                    continue;
+        while position < line.len() {
+            match self.token_at(line, position) {
+                Some((token, len)) => {
+                    tokens.push(token);
+                    position += len;
                 }
+                None => position += line[position..].chars().next().unwrap().len_utf8(),
+            }
+        }
 
-                // println!("{0:?} - Expected {1:?}, but found  {2:?} instead.", line, expected_closing, token);
+        tokens
+    }
 
-                *illegal_char_count.entry(token).or_insert(0) += 1;
-                continue 'next_line;
+    fn check(&self, line: &str) -> Check {
+        let mut open = Vec::new();
+
+        for token in self.tokenize(line) {
+            match token {
+                // Opening delimiters go on the stack
+                Token::Opening(pair) => open.push(pair),
+                // Closing ones have to match the top of the stack
+                Token::Closing(pair) => match open.pop() {
+                    Some(expected) if expected == pair => (),
+                    expected => return Check::Corrupted { found: pair, expected },
+                },
             }
         }
 
-        // Incomplete lines have missing closing chars
-        let mut completion_score: usize = 0;
+        if open.is_empty() {
+            Check::Complete
+        } else {
+            open.reverse();
+            Check::Incomplete(open)
+        }
+    }
 
-        for head in this_line.iter() {
-            let closing_char = valid_pairs.get(head)
-                .expect(format!("Could not determine closing char for head={0:?}", head).as_str());
+    fn completion_score(&self, open: &[usize]) -> u64 {
+        open.iter()
+            .try_fold(0u64, |score, &pair| {
+                score.checked_mul(self.completion_multiplier)?.checked_add(self.pairs[pair].completion_score)
+            })
+            .expect("Completion score overflows")
+    }
 
-            let char_score = match closing_char {
-                ')' => 1,
-                ']' => 2,
-                '}' => 3,
-                '>' => 4,
-                _ => 0,
-            };
+    // The total syntax error score of the corrupted lines and the middle completion score of the incomplete ones
+    fn scores(&self, lines: &[String]) -> (u64, Option<u64>) {
+        let mut total_syntax_error_score = 0;
+        let mut completed_scores = Vec::new();
 
-            completion_score = completion_score * 5 + char_score;
-            // println!("closing with {0:?} for {1:?} points -> completion_score={2:?}", closing_char, char_score, completion_score);
+        for line in lines {
+            match self.check(line) {
+                Check::Complete => (),
+                Check::Incomplete(open) => completed_scores.push(self.completion_score(&open)),
+                Check::Corrupted { found, expected: _ } => total_syntax_error_score += self.pairs[found].corruption_score,
+            }
         }
 
-        if completion_score > 0 {
-            // println!("completing {0:?} - {1:?} total points.", this_line, completion_score);
-            completed_scores.push(completion_score);
+        completed_scores.sort_unstable();
+        // println!("completed_scores={0:?}", completed_scores);
+
+        (total_syntax_error_score, completed_scores.get(completed_scores.len() / 2).copied())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    grammar_path: Option<String>,
+}
+
+impl Options {
+    fn from_args() -> Self {
+        let mut options = Self::default();
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--grammar" => options.grammar_path = Some(args.next().expect("Missing path for --grammar")),
+                _ => panic!("Invalid argument: {0:?}", arg),
+            }
         }
+
+        options
     }
+}
+
+fn main() {
+    let options = Options::from_args();
+
+    let lines: Vec<String> = stdin().lock().lines()
+        .map(|line| line.expect("Cannot read line"))
+        .collect();
 
-    // println!("illegal_char_count={0:?}", illegal_char_count);
+    if let Some(path) = &options.grammar_path {
+        let grammar = Grammar::from_file(path);
 
-    completed_scores.sort();
-    // println!("completed_scores={0:?}", completed_scores);
+        for (index, line) in lines.iter().enumerate() {
+            let describe = |pair: usize| format!("{0:?}", grammar.pairs[pair].closing);
 
-    let total_syntax_error_score: i32 = illegal_char_count.iter()
-        .map(|(illegal_char, count)| {
-            match illegal_char {
-                ')' => count * 3,
-                ']' => count * 57,
-                '}' => count * 1197,
-                '>' => count * 25137,
-                _ => 0,
+            match grammar.check(line) {
+                Check::Complete => println!("line={0:?} complete", index + 1),
+                Check::Incomplete(open) => {
+                    let completion = open.iter().map(|&pair| grammar.pairs[pair].closing.as_str()).collect::<Vec<&str>>().join(" ");
+                    println!("line={0:?} incomplete completion={1:?} score={2:?}", index + 1, completion, grammar.completion_score(&open));
+                }
+                Check::Corrupted { found, expected } => {
+                    println!("line={0:?} corrupted expected={1} found={2}", index + 1,
+                             expected.map_or("nothing".to_owned(), describe), describe(found));
+                }
             }
-        })
-        .sum();
+        }
+
+        let (total_syntax_error_score, middle_score) = grammar.scores(&lines);
+        println!("grammar: total_syntax_error_score={0:?} middle_score={1:?}", total_syntax_error_score, middle_score);
+        return;
+    }
+
+    let (total_syntax_error_score, middle_score) = Grammar::brackets().scores(&lines);
     println!("part1: total_syntax_error_score={0:?}", total_syntax_error_score);
     assert_eq!(total_syntax_error_score, 240123);
 
-    let middle_score = completed_scores[completed_scores.len() / 2];
+    let middle_score = middle_score.expect("No incomplete lines");
     println!("part2: middle_score={0:?}", middle_score);
     assert_eq!(middle_score, 3260812321);
 }